@@ -0,0 +1,38 @@
+use async_std::task;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use manning_lp_async_rust_project_1_m1::analysis::compute_all;
+use manning_lp_async_rust_project_1_m1::signals::*;
+
+///
+/// The same signals as `compute_all`, calculated one after the other.
+///
+async fn compute_serial(series: &[f64], sma_window: usize) {
+    MaxPrice {}.calculate(series).await;
+    MinPrice {}.calculate(series).await;
+    PriceDifference {}.calculate(series).await;
+    WindowedSMA {
+        window_size: sma_window,
+    }
+    .calculate(series)
+    .await;
+    ExponentialMovingAverage { period: 30 }
+        .calculate(series)
+        .await;
+}
+
+fn bench_compute_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signals");
+    for len in [1_000, 100_000, 1_000_000] {
+        let series: Vec<f64> = (0..len).map(|i| 100.0 + (i as f64).sin()).collect();
+        group.bench_with_input(BenchmarkId::new("serial", len), &series, |b, s| {
+            b.iter(|| task::block_on(compute_serial(s, 200)))
+        });
+        group.bench_with_input(BenchmarkId::new("compute_all", len), &series, |b, s| {
+            b.iter(|| task::block_on(compute_all(s, 200)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_compute_all);
+criterion_main!(benches);
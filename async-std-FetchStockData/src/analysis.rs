@@ -0,0 +1,349 @@
+//!
+//! Calculation of several signals over the same series at once, and comparisons of series.
+//!
+use crate::data::Quote;
+use crate::signals::*;
+use async_std::task;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// The signals shown for a symbol, calculated over its closing prices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub last_price: f64,
+    /// The change over the whole period in percent.
+    pub change_pct: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The averages, empty rather than zero if the series is too short for any of them.
+    pub sma: Vec<f64>,
+    pub ema: Vec<f64>,
+}
+
+///
+/// Run a signal on its own task, so independent signals are calculated in parallel.
+///
+fn spawn_signal<S>(signal: S, series: &Arc<[f64]>) -> task::JoinHandle<Option<S::SignalType>>
+where
+    S: StockSignal + Send + Sync + 'static,
+    S::SignalType: Send + 'static,
+{
+    let series = series.clone();
+    task::spawn(async move { signal.calculate(&series).await })
+}
+
+/// The number of prices the EMA of a `Summary` is calculated over.
+pub const EMA_PERIOD: usize = 30;
+
+///
+/// Calculate all signals of a `Summary` concurrently, with an SMA over `sma_window` prices
+/// and an EMA over `EMA_PERIOD` ones. Returns None if there are no prices to work with.
+///
+pub async fn compute_all(series: &[f64], sma_window: usize) -> Option<Summary> {
+    let last_price = *series.last()?;
+    let series: Arc<[f64]> = series.into();
+    let (max, min, difference, sma, ema) = futures::join!(
+        spawn_signal(MaxPrice {}, &series),
+        spawn_signal(MinPrice {}, &series),
+        spawn_signal(PriceDifference {}, &series),
+        spawn_signal(
+            WindowedSMA {
+                window_size: sma_window
+            },
+            &series
+        ),
+        spawn_signal(ExponentialMovingAverage { period: EMA_PERIOD }, &series),
+    );
+    let (_, rel_change) = difference?;
+    Some(Summary {
+        last_price,
+        change_pct: rel_change * 100.0,
+        min: min?,
+        max: max?,
+        sma: sma.unwrap_or_default(),
+        ema: ema.unwrap_or_default(),
+    })
+}
+
+///
+/// The change of `quotes` minus the change of `benchmark`, both as fractions over the
+/// time the two overlap. Returns None if they don't overlap.
+///
+pub async fn relative_change(quotes: &[Quote], benchmark: &[Quote]) -> Option<f64> {
+    let start = quotes.first()?.timestamp.max(benchmark.first()?.timestamp);
+    let end = quotes.last()?.timestamp.min(benchmark.last()?.timestamp);
+    let change_over_overlap = |quotes: &[Quote]| {
+        let overlap: Vec<f64> = quotes
+            .iter()
+            .filter(|q| q.timestamp >= start && q.timestamp <= end)
+            .map(|q| q.close)
+            .collect();
+        async move { PriceDifference {}.calculate(&overlap).await }
+    };
+    let (_, change) = change_over_overlap(quotes).await?;
+    let (_, benchmark_change) = change_over_overlap(benchmark).await?;
+    Some(change - benchmark_change)
+}
+
+///
+/// The closes of both series on the timestamps they have in common, e.g. leaving out the
+/// holidays of only one exchange. Both must be sorted by time.
+///
+pub fn aligned_closes(a: &[Quote], b: &[Quote]) -> (Vec<f64>, Vec<f64>) {
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    let mut aligned = (vec![], vec![]);
+    while let (Some(qa), Some(qb)) = (a.peek(), b.peek()) {
+        match qa.timestamp.cmp(&qb.timestamp) {
+            std::cmp::Ordering::Less => {
+                a.next();
+            }
+            std::cmp::Ordering::Greater => {
+                b.next();
+            }
+            std::cmp::Ordering::Equal => {
+                aligned.0.push(qa.close);
+                aligned.1.push(qb.close);
+                a.next();
+                b.next();
+            }
+        }
+    }
+    aligned
+}
+
+///
+/// The value of holding all series, as an index starting at 100 on the first timestamp they all
+/// have quotes for. The weights are of the starting value, the holdings aren't rebalanced
+/// afterwards, and only the timestamps all series have in common are used. The series must be
+/// sorted by time. Returns None if the weights don't match the series or sum to zero, or there
+/// is no common timestamp with a close other than zero.
+///
+pub fn portfolio_index(series: &[&[Quote]], weights: &[f64]) -> Option<Vec<f64>> {
+    let total: f64 = weights.iter().sum();
+    if series.is_empty() || series.len() != weights.len() || total == 0.0 {
+        return None;
+    }
+    let mut common: Vec<_> = series[0].iter().map(|q| q.timestamp).collect();
+    for quotes in &series[1..] {
+        let timestamps: HashSet<_> = quotes.iter().map(|q| q.timestamp).collect();
+        common.retain(|t| timestamps.contains(t));
+    }
+    let closes: Vec<Vec<f64>> = series
+        .iter()
+        .map(|quotes| {
+            quotes
+                .iter()
+                .filter(|q| common.binary_search(&q.timestamp).is_ok())
+                .map(|q| q.close)
+                .collect()
+        })
+        .collect();
+    if closes
+        .iter()
+        .any(|closes| closes.first().is_none_or(|c| *c == 0.0))
+    {
+        return None;
+    }
+    Some(
+        (0..common.len())
+            .map(|t| {
+                let value: f64 = closes
+                    .iter()
+                    .zip(weights)
+                    .map(|(closes, weight)| weight * closes[t] / closes[0])
+                    .sum();
+                100.0 * value / total
+            })
+            .collect(),
+    )
+}
+
+///
+/// The Pearson correlation of the returns of two aligned closing series, see `aligned_closes`.
+/// Returns None if the series differ in length, have fewer than 3 prices, i.e. 2 returns, or
+/// either doesn't move at all.
+///
+pub fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    if a.len() != b.len() || a.len() < 3 {
+        return None;
+    }
+    let (returns_a, returns_b) = (simple_returns(a), simple_returns(b));
+    let (_, std_dev_a) = mean_and_std_dev(&returns_a);
+    let (_, std_dev_b) = mean_and_std_dev(&returns_b);
+    if std_dev_a == 0.0 || std_dev_b == 0.0 {
+        return None;
+    }
+    Some(covariance(&returns_a, &returns_b) / (std_dev_a * std_dev_b))
+}
+
+///
+/// The sample covariance of two series of the same length, with at least two values.
+///
+fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, _) = mean_and_std_dev(a);
+    let (mean_b, _) = mean_and_std_dev(b);
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - mean_a) * (y - mean_b))
+        .sum::<f64>()
+        / (a.len() - 1) as f64
+}
+
+///
+/// The beta of a symbol, i.e. the covariance of its returns with the benchmark's divided by
+/// the variance of the benchmark's, for returns over the same periods. Returns None if they
+/// differ in length, there are fewer than 2 of them or the benchmark doesn't move at all.
+///
+pub fn beta(symbol_returns: &[f64], benchmark_returns: &[f64]) -> Option<f64> {
+    if symbol_returns.len() != benchmark_returns.len() || symbol_returns.len() < 2 {
+        return None;
+    }
+    let (_, std_dev) = mean_and_std_dev(benchmark_returns);
+    if std_dev == 0.0 {
+        return None;
+    }
+    Some(covariance(symbol_returns, benchmark_returns) / std_dev.powi(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_compute_all() {
+        assert_eq!(compute_all(&[], 2).await, None);
+
+        let summary = compute_all(&[2.0, 1.0, 4.0], 2).await.unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                last_price: 4.0,
+                change_pct: 100.0,
+                min: 1.0,
+                max: 4.0,
+                sma: vec![1.5, 2.5],
+                ema: vec![],
+            }
+        );
+
+        let series: Vec<f64> = (1..=EMA_PERIOD + 1).map(|p| p as f64).collect();
+        let summary = compute_all(&series, 2).await.unwrap();
+        assert_eq!(summary.last_price, 31.0);
+        assert_eq!(summary.change_pct, 3000.0);
+        assert_eq!((summary.min, summary.max), (1.0, 31.0));
+        assert_eq!(summary.sma.len(), EMA_PERIOD);
+        assert_eq!(summary.sma.last(), Some(&30.5));
+        assert_eq!(summary.ema.len(), 2);
+        assert!(summary.ema.iter().all(|ema| (1.0..=31.0).contains(ema)));
+    }
+
+    #[async_std::test]
+    async fn test_relative_change() {
+        use chrono::prelude::*;
+        let quote = |d, close| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 0, 0, 0).unwrap(),
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let quotes = [quote(4, 1.0), quote(5, 2.0), quote(6, 4.0)];
+        let benchmark = [quote(5, 10.0), quote(6, 11.0), quote(7, 12.0)];
+        // only the 5th and 6th overlap: +100% vs. +10%
+        let change = relative_change(&quotes, &benchmark).await.unwrap();
+        assert!((change - 0.9).abs() < 1e-9);
+        assert_eq!(relative_change(&quotes, &quotes).await, Some(0.0));
+        assert_eq!(relative_change(&quotes[..1], &benchmark).await, None);
+        assert_eq!(relative_change(&[], &benchmark).await, None);
+    }
+
+    #[test]
+    fn test_aligned_closes() {
+        use chrono::prelude::*;
+        let quote = |d, close| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 0, 0, 0).unwrap(),
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let a = [quote(4, 1.0), quote(5, 2.0), quote(7, 3.0), quote(8, 4.0)];
+        let b = [quote(5, 20.0), quote(6, 30.0), quote(7, 40.0)];
+        assert_eq!(aligned_closes(&a, &b), (vec![2.0, 3.0], vec![20.0, 40.0]));
+        assert_eq!(aligned_closes(&b, &a), (vec![20.0, 40.0], vec![2.0, 3.0]));
+        assert_eq!(aligned_closes(&a, &[]), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_portfolio_index() {
+        use chrono::prelude::*;
+        let quote = |d, close| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 0, 0, 0).unwrap(),
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let a = [quote(4, 10.0), quote(5, 12.5), quote(7, 12.5)];
+        let b = [quote(4, 20.0), quote(6, 25.0), quote(7, 30.0)];
+        // only the 4th and the 7th are common, a gains 25% and b 50%
+        assert_eq!(
+            portfolio_index(&[&a, &b], &[1.0, 1.0]),
+            Some(vec![100.0, 137.5])
+        );
+        assert_eq!(
+            portfolio_index(&[&a, &b], &[3.0, 1.0]),
+            Some(vec![100.0, 131.25])
+        );
+        assert_eq!(
+            portfolio_index(&[&a], &[2.0]),
+            Some(vec![100.0, 125.0, 125.0])
+        );
+        assert_eq!(portfolio_index(&[&a, &b], &[1.0]), None);
+        assert_eq!(portfolio_index(&[&a, &b], &[1.0, -1.0]), None);
+        assert_eq!(portfolio_index(&[&a, &b[1..2]], &[1.0, 1.0]), None);
+        assert_eq!(portfolio_index(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let a = [100.0, 110.0, 99.0, 108.9];
+        // the same returns, halved, and mirrored
+        let b = [50.0, 52.5, 49.875, 52.36875];
+        let c = [100.0, 90.0, 99.0, 89.1];
+        assert!((correlation(&a, &b).unwrap() - 1.0).abs() < 1e-9);
+        assert!((correlation(&a, &c).unwrap() + 1.0).abs() < 1e-9);
+
+        // returns of 10%, 0% and 10% against 10%, -10% and 10%
+        let d = [100.0, 110.0, 110.0, 121.0];
+        assert!((correlation(&a, &d).unwrap() - 1.0).abs() < 1e-9);
+        let e = [100.0, 110.0, 121.0, 121.0];
+        assert!((correlation(&a, &e).unwrap() + 0.5).abs() < 1e-9);
+
+        assert_eq!(correlation(&a, &b[..3]), None);
+        assert_eq!(correlation(&a[..2], &b[..2]), None);
+        assert_eq!(correlation(&a, &[5.0, 5.0, 5.0, 5.0]), None);
+    }
+
+    #[test]
+    fn test_beta() {
+        let benchmark = [0.1, -0.1, 0.1];
+        // twice the moves, half of them, and opposite ones
+        let b = beta(&[0.2, -0.2, 0.2], &benchmark).unwrap();
+        assert!((b - 2.0).abs() < 1e-9);
+        let b = beta(&[0.06, -0.04, 0.06], &benchmark).unwrap();
+        assert!((b - 0.5).abs() < 1e-9);
+        let b = beta(&[-0.1, 0.1, -0.1], &benchmark).unwrap();
+        assert!((b + 1.0).abs() < 1e-9);
+        // moves unrelated to the benchmark
+        let b = beta(&[0.05, 0.05, 0.05], &benchmark).unwrap();
+        assert!(b.abs() < 1e-9);
+
+        assert_eq!(beta(&[0.1, 0.2], &benchmark), None);
+        assert_eq!(beta(&[0.1], &[0.1]), None);
+        assert_eq!(beta(&[0.1, 0.2, 0.3], &[0.01, 0.01, 0.01]), None);
+    }
+}
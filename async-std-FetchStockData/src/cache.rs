@@ -0,0 +1,308 @@
+//!
+//! A file-based cache for closing prices, avoiding redundant downloads.
+//!
+use crate::data::{Bar, FetchError, PriceField, Quote, StockDataProvider};
+use async_std::fs;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use log::debug;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Wraps another provider and stores its quotes as JSON files in `dir`,
+/// serving them from there for up to `ttl`.
+pub struct CachedProvider {
+    pub inner: Arc<dyn StockDataProvider>,
+    pub dir: PathBuf,
+    pub ttl: Duration,
+    /// The bar size and price field the inner provider fetches, so their quotes don't mix.
+    pub bar: Bar,
+    pub price_field: PriceField,
+}
+
+impl CachedProvider {
+    ///
+    /// The cache file for a request, named after the symbol, the bar size, the price field and
+    /// the range. Ranges ending within the TTL of now are open-ended, so they share a "latest"
+    /// entry instead of getting a new one for every run.
+    ///
+    fn path(&self, symbol: &str, from: &DateTime<Utc>, to: &DateTime<Utc>) -> PathBuf {
+        let open_ended = Utc::now()
+            .signed_duration_since(*to)
+            .to_std()
+            .map_or(true, |age| age < self.ttl);
+        let end = if open_ended {
+            "latest".to_string()
+        } else {
+            to.timestamp().to_string()
+        };
+        self.dir.join(format!(
+            "{}_{}_{}_{}_{}.json",
+            symbol,
+            self.bar,
+            self.price_field.as_str(),
+            from.timestamp(),
+            end
+        ))
+    }
+
+    ///
+    /// Read a cache entry if it exists and is younger than the TTL.
+    ///
+    async fn read(&self, path: &PathBuf) -> Option<Vec<Quote>> {
+        let modified = fs::metadata(path).await.ok()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age >= self.ttl {
+            return None;
+        }
+        serde_json::from_slice(&fs::read(path).await.ok()?).ok()
+    }
+}
+
+#[async_trait]
+impl StockDataProvider for CachedProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        let path = self.path(symbol, from, to);
+        if let Some(quotes) = self.read(&path).await {
+            debug!("cache hit for {} at {}", symbol, path.display());
+            return Ok(quotes);
+        }
+        debug!("cache miss for {}", symbol);
+        let quotes = self.inner.fetch_quotes(symbol, from, to).await?;
+        // the cache is best effort, failing to write it doesn't fail the fetch
+        if let (Ok(()), Ok(json)) = (
+            fs::create_dir_all(&self.dir).await,
+            serde_json::to_vec(&quotes),
+        ) {
+            if let Err(e) = fs::write(&path, json).await {
+                debug!("couldn't write {}: {}", path.display(), e);
+            }
+        }
+        Ok(quotes)
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        // a symbol keeps its currency, so these entries don't expire
+        let path = self.dir.join(format!("{}.currency", symbol));
+        if let Ok(currency) = fs::read_to_string(&path).await {
+            return Ok(Some(currency));
+        }
+        let currency = self.inner.fetch_currency(symbol).await?;
+        if let Some(currency) = &currency {
+            if let Err(e) = fs::create_dir_all(&self.dir).await {
+                debug!("couldn't create {}: {}", self.dir.display(), e);
+            } else if let Err(e) = fs::write(&path, currency).await {
+                debug!("couldn't write {}: {}", path.display(), e);
+            }
+        }
+        Ok(currency)
+    }
+}
+
+/// Wraps another provider and remembers the currency of each symbol it found for as long as it
+/// lives, so repeated fetches, e.g. while polling, look it up only once.
+pub struct CurrencyMemo {
+    pub inner: Arc<dyn StockDataProvider>,
+    currencies: Mutex<HashMap<String, String>>,
+}
+
+impl CurrencyMemo {
+    pub fn new(inner: Arc<dyn StockDataProvider>) -> Self {
+        CurrencyMemo {
+            inner,
+            currencies: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl StockDataProvider for CurrencyMemo {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        self.inner.fetch_quotes(symbol, from, to).await
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        if let Some(currency) = self.currencies.lock().unwrap().get(symbol) {
+            return Ok(Some(currency.clone()));
+        }
+        // like in the file cache, an unknown currency is asked for again
+        let currency = self.inner.fetch_currency(symbol).await?;
+        if let Some(currency) = &currency {
+            self.currencies
+                .lock()
+                .unwrap()
+                .insert(symbol.to_string(), currency.clone());
+        }
+        Ok(currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl StockDataProvider for CountingProvider {
+        async fn fetch_quotes(
+            &self,
+            _symbol: &str,
+            from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(vec![Quote {
+                timestamp: *from,
+                high: calls as f64,
+                low: calls as f64,
+                close: calls as f64,
+                volume: 0,
+                adjustment: None,
+            }])
+        }
+
+        async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok((symbol == "SAP").then(|| "EUR".to_string()))
+        }
+    }
+
+    #[async_std::test]
+    async fn test_CachedProvider_fetch_quotes() {
+        let dir = std::env::temp_dir().join("test_CachedProvider_fetch_quotes");
+        let _ = std::fs::remove_dir_all(&dir);
+        let inner = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let provider = CachedProvider {
+            inner: inner.clone(),
+            dir: dir.clone(),
+            ttl: Duration::from_secs(60),
+            bar: Bar::Day1,
+            price_field: PriceField::AdjClose,
+        };
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 2, 4, 0, 0, 0).unwrap();
+        let quote = |close| Quote {
+            timestamp: from,
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+
+        let series = provider.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(1.0)]);
+        let series = provider.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(1.0)]);
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        // a different key misses
+        let series = provider.fetch_quotes("MSFT", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(2.0)]);
+        let intraday = CachedProvider {
+            inner: inner.clone(),
+            dir: dir.clone(),
+            ttl: Duration::from_secs(60),
+            bar: Bar::Minute5,
+            price_field: PriceField::AdjClose,
+        };
+        let series = intraday.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(3.0)]);
+        let unadjusted = CachedProvider {
+            price_field: PriceField::Close,
+            ..intraday
+        };
+        let series = unadjusted.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(4.0)]);
+
+        // ranges up to now share an entry
+        let (earlier, now) = (Utc::now() - chrono::Duration::seconds(5), Utc::now());
+        provider
+            .fetch_quotes("AAPL", &from, &earlier)
+            .await
+            .unwrap();
+        provider.fetch_quotes("AAPL", &from, &now).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 5);
+
+        // expired entries are fetched again
+        let provider = CachedProvider {
+            ttl: Duration::from_secs(0),
+            ..provider
+        };
+        let series = provider.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        assert_eq!(series, vec![quote(6.0)]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_CachedProvider_fetch_currency() {
+        let dir = std::env::temp_dir().join("test_CachedProvider_fetch_currency");
+        let _ = std::fs::remove_dir_all(&dir);
+        let inner = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let provider = CachedProvider {
+            inner: inner.clone(),
+            dir: dir.clone(),
+            ttl: Duration::from_secs(0),
+            bar: Bar::Day1,
+            price_field: PriceField::AdjClose,
+        };
+
+        for _ in 0..2 {
+            let currency = provider.fetch_currency("SAP").await.unwrap();
+            assert_eq!(currency.as_deref(), Some("EUR"));
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        // an unknown currency is asked for again
+        for _ in 0..2 {
+            assert_eq!(provider.fetch_currency("AAPL").await.unwrap(), None);
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[async_std::test]
+    async fn test_CurrencyMemo_fetch_currency() {
+        let inner = Arc::new(CountingProvider {
+            calls: AtomicUsize::new(0),
+        });
+        let provider = CurrencyMemo::new(inner.clone());
+        for _ in 0..2 {
+            let currency = provider.fetch_currency("SAP").await.unwrap();
+            assert_eq!(currency.as_deref(), Some("EUR"));
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+        for _ in 0..2 {
+            assert_eq!(provider.fetch_currency("AAPL").await.unwrap(), None);
+        }
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+        // the quotes are passed through as they are
+        let now = Utc::now();
+        provider.fetch_quotes("SAP", &now, &now).await.unwrap();
+        provider.fetch_quotes("SAP", &now, &now).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 5);
+    }
+}
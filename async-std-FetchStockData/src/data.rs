@@ -0,0 +1,1011 @@
+//!
+//! Retrieval of closing prices from a data source.
+//!
+use async_std::task;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use yahoo_finance_api as yahoo;
+
+/// Why fetching the data for a symbol failed.
+#[derive(Debug, Error)]
+pub enum FetchError {
+    /// Downloading the data failed, possibly only temporarily.
+    #[error("download failed: {0}")]
+    Network(#[source] yahoo::YahooError),
+    /// The response couldn't be understood.
+    #[error("couldn't parse the response: {0}")]
+    Parse(#[source] yahoo::YahooError),
+    /// The data source has no data for the symbol and period.
+    #[error("no data available")]
+    NoData,
+    /// A local data file couldn't be read.
+    #[error("couldn't read the data file: {0}")]
+    File(#[source] std::io::Error),
+    /// The data source doesn't keep bars of this size for that long.
+    #[error("{bar} bars are only available for the last {days} days")]
+    OutOfRange { bar: Bar, days: i64 },
+    /// The data source refused the request with this HTTP status, e.g. 404 for an unknown
+    /// symbol or 429 to slow down.
+    #[error("the data source answered with HTTP status {0}")]
+    Http(u16),
+    /// The data source didn't answer in time.
+    #[error("no answer within {0:?}")]
+    Timeout(Duration),
+}
+
+impl From<yahoo::YahooError> for FetchError {
+    fn from(error: yahoo::YahooError) -> Self {
+        match error {
+            yahoo::YahooError::FetchFailed(ref message) => match status_code(message) {
+                // server errors are likely gone on the next attempt, anything else is up to us
+                Some(status) if !(500..600).contains(&status) => FetchError::Http(status),
+                _ => FetchError::Network(error),
+            },
+            yahoo::YahooError::ConnectionFailed | yahoo::YahooError::InvalidJson => {
+                FetchError::Network(error)
+            }
+            yahoo::YahooError::EmptyDataSet => FetchError::NoData,
+            yahoo::YahooError::DeserializeFailed(_) | yahoo::YahooError::DataInconsistency => {
+                FetchError::Parse(error)
+            }
+        }
+    }
+}
+
+///
+/// Run `op` until it succeeds, retrying up to `max_retries` times with an exponential
+/// backoff (100ms, 200ms, 400ms, ... capped at ~100s) for as long as `retry` considers the
+/// error transient.
+///
+async fn retry_with_backoff<T, E, F, Fut>(
+    max_retries: u32,
+    retry: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if attempt < max_retries && retry(&e) => {
+                let delay = Duration::from_millis(100 << attempt.min(10));
+                warn!(
+                    "attempt {} failed: {}, retrying in {:?}",
+                    attempt + 1,
+                    e,
+                    delay
+                );
+                task::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+///
+/// The HTTP status in the message of a failed fetch, e.g. "Status Code: 404 Not Found".
+///
+fn status_code(message: &str) -> Option<u16> {
+    message
+        .split_whitespace()
+        .find_map(|word| word.parse().ok())
+}
+
+/// The kinds of errors that are worth another attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transient {
+    /// The connection failed, the response was cut short or the server had an error.
+    Network,
+    /// The data source asked to slow down with HTTP 429.
+    RateLimited,
+}
+
+impl FromStr for Transient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "network" => Ok(Transient::Network),
+            "rate-limit" => Ok(Transient::RateLimited),
+            _ => Err(format!(
+                "unknown kind of error '{}', expected network or rate-limit",
+                s
+            )),
+        }
+    }
+}
+
+/// All kinds of transient errors.
+pub const ALL_TRANSIENT: &[Transient] = &[Transient::Network, Transient::RateLimited];
+
+impl FetchError {
+    ///
+    /// The kind of a transient error, or None for a permanent one that fails again the same
+    /// way, like an unknown symbol. A timeout ends all attempts, so it isn't retried either.
+    ///
+    pub fn transient(&self) -> Option<Transient> {
+        match self {
+            FetchError::Network(_) => Some(Transient::Network),
+            FetchError::Http(429) => Some(Transient::RateLimited),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Whether a quote has the price of `price_field` to work with. Some responses contain NaN or
+/// infinite prices, and a missing price is reported as 0.0, either would poison min, max,
+/// averages etc. Adjusted prices also need the unadjusted close, which high and low are scaled
+/// with.
+///
+fn is_usable(quote: &yahoo::Quote, price_field: PriceField) -> bool {
+    let positive = |price: f64| price.is_finite() && price > 0.0;
+    match price_field {
+        PriceField::AdjClose => positive(quote.adjclose) && positive(quote.close),
+        PriceField::Close => positive(quote.close),
+    }
+}
+
+///
+/// Collapse sorted quotes with the same timestamp, e.g. pre-market and regular, into the last
+/// of them, so they aren't counted twice.
+///
+fn dedup_timestamps(quotes: &mut Vec<yahoo::Quote>) {
+    quotes.dedup_by(|later, kept| {
+        let duplicate = later.timestamp == kept.timestamp;
+        if duplicate {
+            std::mem::swap(later, kept);
+        }
+        duplicate
+    });
+}
+
+///
+/// Retrieve the quotes from Yahoo! finance in bars of the given size, sorted by their timestamp.
+/// Transient download errors of the kinds in `retry_on` are retried up to `max_retries` times,
+/// quotes without a usable `price_field` are dropped, see `is_usable`, and duplicates are
+/// collapsed.
+///
+async fn download_quotes(
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    bar: Bar,
+    max_retries: u32,
+    retry_on: &[Transient],
+    price_field: PriceField,
+) -> Result<Vec<yahoo::Quote>, FetchError> {
+    let retry = |e: &FetchError| e.transient().is_some_and(|kind| retry_on.contains(&kind));
+    let response = retry_with_backoff(max_retries, retry, || {
+        let (symbol, beginning, end) = (symbol.to_string(), *beginning, *end);
+        // the blocking connector must not stall the executor while other symbols are fetched
+        task::spawn_blocking(move || {
+            let provider = yahoo::YahooConnector::new();
+            Ok(provider.get_quote_history_interval(&symbol, beginning, end, bar.as_str())?)
+        })
+    })
+    .await?;
+    let mut quotes = response.quotes()?;
+    let count = quotes.len();
+    quotes.retain(|q| is_usable(q, price_field));
+    if quotes.len() < count {
+        debug!(
+            "dropped {} unusable quotes of {}",
+            count - quotes.len(),
+            symbol
+        );
+    }
+    // stable, so duplicates keep their order
+    quotes.sort_by_cached_key(|k| k.timestamp);
+    dedup_timestamps(&mut quotes);
+    Ok(quotes)
+}
+
+/// Which of the closing prices to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceField {
+    /// The close adjusted for splits and dividends, so the prices before them are scaled to be
+    /// comparable with the prices after them. Returns and averages over such events are right.
+    AdjClose,
+    /// The close as it was traded then, e.g. to match a broker statement. A split shows up as
+    /// a sudden drop and dividends aren't accounted for.
+    Close,
+}
+
+impl PriceField {
+    /// The name of the field, as parsed by `from_str`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceField::AdjClose => "adjclose",
+            PriceField::Close => "close",
+        }
+    }
+
+    /// The chosen closing price of a quote.
+    fn of(self, quote: &yahoo::Quote) -> f64 {
+        match self {
+            PriceField::AdjClose => quote.adjclose,
+            PriceField::Close => quote.close,
+        }
+    }
+}
+
+impl FromStr for PriceField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adjclose" => Ok(PriceField::AdjClose),
+            "close" => Ok(PriceField::Close),
+            _ => Err(format!(
+                "unknown price field '{}', expected adjclose or close",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// Retrieve the quotes of a symbol from the provider and extract the timestamps with the closing
+/// prices, sorted by time. The bar size, retries and price field are the provider's, e.g. those
+/// of a `YahooProvider`.
+///
+pub async fn fetch_quote_series(
+    provider: &dyn StockDataProvider,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<Vec<(DateTime<Utc>, f64)>, FetchError> {
+    let quotes = provider.fetch_quotes(symbol, beginning, end).await?;
+    Ok(quotes.iter().map(|q| (q.timestamp, q.close)).collect())
+}
+
+///
+/// Retrieve the closing prices of a symbol from the provider, like `fetch_quote_series`
+/// without the timestamps.
+///
+pub async fn fetch_closing_data(
+    provider: &dyn StockDataProvider,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<Vec<f64>, FetchError> {
+    let series = fetch_quote_series(provider, symbol, beginning, end).await?;
+    Ok(series.into_iter().map(|(_, price)| price).collect())
+}
+
+/// The data of a single trading period.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub timestamp: DateTime<Utc>,
+    /// The highest price, adjusted like the closing price.
+    pub high: f64,
+    /// The lowest price, adjusted like the closing price.
+    pub low: f64,
+    /// The closing price, usually adjusted for splits and dividends, see `PriceField`.
+    pub close: f64,
+    /// The number of shares traded.
+    pub volume: u64,
+    /// The adjusted close divided by the unadjusted one, if the source has both. It changes
+    /// between two quotes when the source adjusted for a split or dividend in between.
+    #[serde(default)]
+    pub adjustment: Option<f64>,
+}
+
+/// A source of quotes, e.g. Yahoo! finance or a local stub.
+#[async_trait]
+pub trait StockDataProvider: Send + Sync {
+    /// The quotes of the period, sorted by time.
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError>;
+
+    /// The currency the prices of the symbol are quoted in, e.g. "USD", if the source knows it.
+    async fn fetch_currency(&self, _symbol: &str) -> Result<Option<String>, FetchError> {
+        Ok(None)
+    }
+}
+
+/// The size of the bars to request, from a minute to a day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bar {
+    Minute1,
+    Minute5,
+    Minute15,
+    Minute30,
+    Hour1,
+    Day1,
+}
+
+impl Bar {
+    /// The name Yahoo! finance uses for the bar size.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bar::Minute1 => "1m",
+            Bar::Minute5 => "5m",
+            Bar::Minute15 => "15m",
+            Bar::Minute30 => "30m",
+            Bar::Hour1 => "1h",
+            Bar::Day1 => "1d",
+        }
+    }
+
+    /// How many days back Yahoo! finance keeps intraday bars, daily ones go back all the way.
+    pub fn max_days(&self) -> Option<i64> {
+        match self {
+            Bar::Minute1 => Some(30),
+            Bar::Minute5 | Bar::Minute15 | Bar::Minute30 => Some(60),
+            Bar::Hour1 => Some(730),
+            Bar::Day1 => None,
+        }
+    }
+}
+
+impl Display for Bar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Bar {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1m" => Ok(Bar::Minute1),
+            "5m" => Ok(Bar::Minute5),
+            "15m" => Ok(Bar::Minute15),
+            "30m" => Ok(Bar::Minute30),
+            "1h" | "60m" => Ok(Bar::Hour1),
+            "1d" => Ok(Bar::Day1),
+            _ => Err(format!(
+                "unknown bar size '{}', expected 1m, 5m, 15m, 30m, 1h or 1d",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// Fail early for a period that starts before the data source's first bar of this size, it
+/// would only answer with a vague error or no data at all.
+///
+fn check_range(bar: Bar, from: &DateTime<Utc>, now: &DateTime<Utc>) -> Result<(), FetchError> {
+    match bar.max_days() {
+        Some(days) if *from < *now - chrono::Duration::days(days) => {
+            Err(FetchError::OutOfRange { bar, days })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Closing prices from Yahoo! finance.
+pub struct YahooProvider {
+    pub max_retries: u32,
+    /// The size of the bars, daily ones unless the signals should run on intraday data.
+    pub bar: Bar,
+    /// The kinds of errors that are retried, usually `ALL_TRANSIENT`.
+    pub retry_on: Vec<Transient>,
+    /// Whether the prices are adjusted.
+    pub price_field: PriceField,
+}
+
+#[async_trait]
+impl StockDataProvider for YahooProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        check_range(self.bar, from, &Utc::now())?;
+        let quotes = download_quotes(
+            symbol,
+            from,
+            to,
+            self.bar,
+            self.max_retries,
+            &self.retry_on,
+            self.price_field,
+        )
+        .await?;
+        Ok(quotes
+            .iter()
+            .filter_map(|q| {
+                // scale high and low the same way the close was adjusted, so they stay comparable
+                let close = self.price_field.of(q);
+                let factor = if q.close == 0.0 { 1.0 } else { close / q.close };
+                Some(Quote {
+                    timestamp: DateTime::from_timestamp(q.timestamp as i64, 0)?,
+                    high: q.high * factor,
+                    low: q.low * factor,
+                    close,
+                    volume: q.volume,
+                    // a missing adjusted close doesn't matter for unadjusted prices
+                    adjustment: (q.close != 0.0 && q.adjclose.is_finite() && q.adjclose > 0.0)
+                        .then(|| q.adjclose / q.close),
+                })
+            })
+            .collect())
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        let retry = |e: &FetchError| {
+            e.transient()
+                .is_some_and(|kind| self.retry_on.contains(&kind))
+        };
+        let response = retry_with_backoff(self.max_retries, retry, || {
+            let symbol = symbol.to_string();
+            task::spawn_blocking(move || {
+                let provider = yahoo::YahooConnector::new();
+                Ok(provider.get_latest_quotes(&symbol, "1d")?)
+            })
+        })
+        .await?;
+        Ok(response
+            .chart
+            .result
+            .first()
+            .map(|block| block.meta.currency.clone()))
+    }
+}
+
+/// The hours of an exchange's regular trading session, in its time zone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub timezone: Tz,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl Session {
+    /// The regular session of US equities, 9:30 to 16:00 New York time. Holidays and early
+    /// closes aren't known, they just have no or fewer bars.
+    pub fn us_equities() -> Self {
+        Session {
+            timezone: chrono_tz::America::New_York,
+            open: NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        }
+    }
+
+    /// Whether a bar starting at the timestamp is within the session on a weekday.
+    pub fn contains(&self, timestamp: &DateTime<Utc>) -> bool {
+        let local = timestamp.with_timezone(&self.timezone);
+        let weekday = !matches!(local.weekday(), Weekday::Sat | Weekday::Sun);
+        weekday && local.time() >= self.open && local.time() < self.close
+    }
+}
+
+/// The granularity of a closing price series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interval {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Interval::Daily),
+            "weekly" => Ok(Interval::Weekly),
+            "monthly" => Ok(Interval::Monthly),
+            _ => Err(format!(
+                "unknown interval '{}', expected daily, weekly or monthly",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// Downsample sorted daily quotes to the interval by taking the last quote of
+/// each ISO week or calendar month, with the range and volume over all of its days.
+///
+pub fn downsample(quotes: &[Quote], interval: Interval) -> Vec<Quote> {
+    let bucket = |t: &DateTime<Utc>| match interval {
+        Interval::Daily => None,
+        Interval::Weekly => Some((t.iso_week().year(), t.iso_week().week())),
+        Interval::Monthly => Some((t.year(), t.month())),
+    };
+    let mut periods: Vec<Quote> = vec![];
+    let mut current = None;
+    for quote in quotes {
+        let b = bucket(&quote.timestamp);
+        match periods.last_mut() {
+            Some(last) if b.is_some() && b == current => {
+                *last = Quote {
+                    high: last.high.max(quote.high),
+                    low: last.low.min(quote.low),
+                    volume: last.volume + quote.volume,
+                    ..quote.clone()
+                };
+            }
+            _ => periods.push(quote.clone()),
+        }
+        current = b;
+    }
+    periods
+}
+
+///
+/// The quotes whose close moved more than `threshold_pct` percent either way from the previous
+/// one, with the move in percent. Such jumps are often splits or large dividends in unadjusted
+/// prices, or errors in the data, rather than trading.
+///
+pub fn price_jumps(quotes: &[Quote], threshold_pct: f64) -> Vec<(DateTime<Utc>, f64)> {
+    quotes
+        .windows(2)
+        .filter(|w| w[0].close != 0.0)
+        .map(|w| (w[1].timestamp, (w[1].close / w[0].close - 1.0) * 100.0))
+        .filter(|(_, pct)| pct.abs() > threshold_pct)
+        .collect()
+}
+
+///
+/// The quotes whose unadjusted close moved more than `threshold_pct` percent either way against
+/// the adjusted one since the previous quote, with that move in percent, e.g. -50 for a 2:1
+/// split. Unlike `price_jumps`, real moves that the adjusted close follows are left out. Quotes
+/// without an `adjustment` are skipped.
+///
+pub fn adjustment_jumps(quotes: &[Quote], threshold_pct: f64) -> Vec<(DateTime<Utc>, f64)> {
+    quotes
+        .windows(2)
+        .filter_map(|w| {
+            let (before, after) = (w[0].adjustment?, w[1].adjustment?);
+            (after != 0.0).then(|| (w[1].timestamp, (before / after - 1.0) * 100.0))
+        })
+        .filter(|(_, pct)| pct.abs() > threshold_pct)
+        .collect()
+}
+
+///
+/// Forward-fill sorted quotes onto every calendar day up to `to`, e.g. over weekends and
+/// holidays. A missing day repeats the close of the last day with data, with no range and no
+/// volume, so filled days are not real trades. Days before the first quote stay empty.
+///
+pub fn forward_fill(quotes: &[Quote], to: &DateTime<Utc>) -> Vec<Quote> {
+    let mut filled: Vec<Quote> = vec![];
+    for quote in quotes {
+        if let Some(last) = filled.last() {
+            filled.extend(fill_days(last, quote.timestamp.date_naive()));
+        }
+        filled.push(quote.clone());
+    }
+    if let Some(last) = filled.last() {
+        let end = to.date_naive().succ_opt().unwrap_or(NaiveDate::MAX);
+        filled.extend(fill_days(last, end));
+    }
+    filled
+}
+
+///
+/// Copies of `last` without trades for each day after it and before `until`.
+///
+fn fill_days(last: &Quote, until: NaiveDate) -> Vec<Quote> {
+    let days = until
+        .signed_duration_since(last.timestamp.date_naive())
+        .num_days();
+    (1..days.max(1))
+        .map(|day| Quote {
+            timestamp: last.timestamp + chrono::Duration::days(day),
+            high: last.close,
+            low: last.close,
+            close: last.close,
+            volume: 0,
+            adjustment: last.adjustment,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    #[async_std::test]
+    async fn test_retry_with_backoff() {
+        let attempts = std::cell::Cell::new(0);
+        let flaky = || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                if attempt < 3 {
+                    Err(attempt)
+                } else {
+                    Ok(attempt)
+                }
+            }
+        };
+        assert_eq!(retry_with_backoff(3, |_| true, flaky).await, Ok(3));
+
+        attempts.set(0);
+        assert_eq!(retry_with_backoff(1, |_| true, flaky).await, Err(2));
+
+        attempts.set(0);
+        assert_eq!(retry_with_backoff(3, |_| false, flaky).await, Err(1));
+    }
+
+    struct StubProvider;
+
+    #[async_trait]
+    impl StockDataProvider for StubProvider {
+        async fn fetch_quotes(
+            &self,
+            symbol: &str,
+            from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            let quote = |d: i64, close: f64| Quote {
+                timestamp: *from + chrono::Duration::days(d),
+                high: close + 1.0,
+                low: close - 1.0,
+                close,
+                volume: 10,
+                adjustment: None,
+            };
+            match symbol {
+                "AAPL" => Ok(vec![quote(0, 1.0), quote(1, 2.0)]),
+                _ => Err(FetchError::NoData),
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn test_fetch_quote_series() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 1, 8, 0, 0, 0).unwrap();
+        let series = fetch_quote_series(&StubProvider, "AAPL", &from, &to)
+            .await
+            .unwrap();
+        assert_eq!(
+            series,
+            vec![(from, 1.0), (from + chrono::Duration::days(1), 2.0)]
+        );
+        let closes = fetch_closing_data(&StubProvider, "AAPL", &from, &to)
+            .await
+            .unwrap();
+        assert_eq!(closes, vec![1.0, 2.0]);
+        assert!(matches!(
+            fetch_quote_series(&StubProvider, "MSFT", &from, &to).await,
+            Err(FetchError::NoData)
+        ));
+    }
+
+    #[test]
+    fn test_adjustment_jumps() {
+        let quote = |d, raw: f64, adjusted: f64| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 14, 30, 0).unwrap(),
+            high: adjusted,
+            low: adjusted,
+            close: adjusted,
+            volume: 0,
+            adjustment: Some(adjusted / raw),
+        };
+        // a 2:1 split on the 5th that the adjusted closes smooth over, then a real drop
+        let series = vec![
+            quote(4, 100.0, 50.0),
+            quote(5, 50.0, 50.0),
+            quote(6, 25.0, 25.0),
+        ];
+        assert_eq!(
+            adjustment_jumps(&series, 40.0),
+            vec![(series[1].timestamp, -50.0)]
+        );
+        assert_eq!(
+            price_jumps(&series, 40.0),
+            vec![(series[2].timestamp, -50.0)]
+        );
+        assert_eq!(adjustment_jumps(&series, 60.0), vec![]);
+        let unknown = Quote {
+            adjustment: None,
+            ..series[0].clone()
+        };
+        assert_eq!(
+            adjustment_jumps(&[unknown, series[1].clone()], 40.0),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_price_jumps() {
+        let quote = |d, close| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 14, 30, 0).unwrap(),
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        // a 4:1 split on the 6th, then a recovery from a bad 0.0
+        let series = vec![
+            quote(4, 100.0),
+            quote(5, 110.0),
+            quote(6, 27.5),
+            quote(7, 0.0),
+            quote(8, 28.0),
+        ];
+        assert_eq!(
+            price_jumps(&series, 40.0),
+            vec![(series[2].timestamp, -75.0), (series[3].timestamp, -100.0)]
+        );
+        assert_eq!(price_jumps(&series[..2], 11.0), vec![]);
+        assert_eq!(price_jumps(&series[..2], 9.0).len(), 1);
+    }
+
+    #[test]
+    fn test_downsample() {
+        let quote = |m, d, close: f64| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, m, d, 14, 30, 0).unwrap(),
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+            volume: 10,
+            adjustment: None,
+        };
+        let closes = |quotes: Vec<Quote>| quotes.iter().map(|q| q.close).collect::<Vec<_>>();
+        // Monday 2021-01-25 to Tuesday 2021-02-02
+        let series = vec![
+            quote(1, 25, 1.0),
+            quote(1, 27, 2.0),
+            quote(1, 29, 3.0),
+            quote(2, 1, 4.0),
+            quote(2, 2, 5.0),
+        ];
+        assert_eq!(downsample(&series, Interval::Daily), series);
+        assert_eq!(
+            closes(downsample(&series, Interval::Weekly)),
+            vec![3.0, 5.0]
+        );
+        let monthly = downsample(&series, Interval::Monthly);
+        assert_eq!(
+            monthly,
+            vec![
+                Quote {
+                    high: 4.0,
+                    low: 0.0,
+                    volume: 30,
+                    ..quote(1, 29, 3.0)
+                },
+                Quote {
+                    high: 6.0,
+                    low: 3.0,
+                    volume: 20,
+                    ..quote(2, 2, 5.0)
+                }
+            ]
+        );
+        assert_eq!(
+            closes(downsample(&series[..3], Interval::Monthly)),
+            vec![3.0]
+        );
+        assert_eq!(downsample(&[], Interval::Weekly), vec![]);
+    }
+
+    #[test]
+    fn test_Session_contains() {
+        let session = Session::us_equities();
+        let at = |d, h, m| Utc.with_ymd_and_hms(2021, 1, d, h, m, 0).unwrap();
+        // New York is 5 hours behind UTC in January
+        assert!(!session.contains(&at(4, 14, 29)));
+        assert!(session.contains(&at(4, 14, 30)));
+        assert!(session.contains(&at(4, 20, 59)));
+        assert!(!session.contains(&at(4, 21, 0)));
+        // Saturday
+        assert!(!session.contains(&at(9, 15, 0)));
+        // and 4 hours in July
+        let july = Utc.with_ymd_and_hms(2021, 7, 6, 13, 30, 0).unwrap();
+        assert!(session.contains(&july));
+    }
+
+    #[test]
+    fn test_forward_fill() {
+        let quote = |d, close: f64| Quote {
+            timestamp: Utc.with_ymd_and_hms(2021, 1, d, 14, 30, 0).unwrap(),
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+            volume: 10,
+            adjustment: None,
+        };
+        let filled_quote = |d, close| Quote {
+            high: close,
+            low: close,
+            volume: 0,
+            ..quote(d, close)
+        };
+        // Friday 2021-01-08 to Monday 2021-01-11, until Wednesday
+        let series = vec![quote(7, 1.0), quote(8, 2.0), quote(11, 3.0)];
+        let to = Utc.with_ymd_and_hms(2021, 1, 13, 0, 0, 0).unwrap();
+        assert_eq!(
+            forward_fill(&series, &to),
+            vec![
+                quote(7, 1.0),
+                quote(8, 2.0),
+                filled_quote(9, 2.0),
+                filled_quote(10, 2.0),
+                quote(11, 3.0),
+                filled_quote(12, 3.0),
+                filled_quote(13, 3.0),
+            ]
+        );
+        let last_day = Utc.with_ymd_and_hms(2021, 1, 11, 23, 0, 0).unwrap();
+        assert_eq!(forward_fill(&series, &last_day).len(), 5);
+        assert_eq!(forward_fill(&series[..1], &last_day).len(), 5);
+        assert_eq!(forward_fill(&[], &to), vec![]);
+    }
+
+    #[test]
+    fn test_FetchError_from() {
+        use yahoo::YahooError;
+        assert!(matches!(
+            FetchError::from(YahooError::ConnectionFailed),
+            FetchError::Network(_)
+        ));
+        assert!(matches!(
+            FetchError::from(YahooError::FetchFailed("500".to_string())),
+            FetchError::Network(_)
+        ));
+        assert!(matches!(
+            FetchError::from(YahooError::DataInconsistency),
+            FetchError::Parse(_)
+        ));
+        assert!(matches!(
+            FetchError::from(YahooError::EmptyDataSet),
+            FetchError::NoData
+        ));
+        let status = |message: &str| FetchError::from(YahooError::FetchFailed(message.into()));
+        assert!(matches!(
+            status("Status Code: 404 Not Found"),
+            FetchError::Http(404)
+        ));
+        assert!(matches!(
+            status("Status Code: 503 Service Unavailable"),
+            FetchError::Network(_)
+        ));
+        assert!(matches!(status("no status"), FetchError::Network(_)));
+
+        let transient = |e: FetchError| e.transient();
+        assert_eq!(
+            transient(FetchError::from(YahooError::InvalidJson)),
+            Some(Transient::Network)
+        );
+        assert_eq!(
+            transient(status("Status Code: 429 Too Many Requests")),
+            Some(Transient::RateLimited)
+        );
+        assert_eq!(transient(status("Status Code: 404 Not Found")), None);
+        assert_eq!(transient(FetchError::NoData), None);
+        assert_eq!(transient(FetchError::Timeout(Duration::from_secs(1))), None);
+    }
+
+    #[test]
+    fn test_PriceField() {
+        assert_eq!("close".parse(), Ok(PriceField::Close));
+        assert!("open".parse::<PriceField>().is_err());
+        let quote = yahoo::Quote {
+            timestamp: 0,
+            open: 0.0,
+            high: 0.0,
+            low: 0.0,
+            volume: 0,
+            close: 10.0,
+            adjclose: 9.5,
+        };
+        assert_eq!(PriceField::AdjClose.of(&quote), 9.5);
+        assert_eq!(PriceField::Close.of(&quote), 10.0);
+    }
+
+    #[test]
+    fn test_Transient_from_str() {
+        assert_eq!("rate-limit".parse(), Ok(Transient::RateLimited));
+        assert!("timeout".parse::<Transient>().is_err());
+    }
+
+    #[test]
+    fn test_is_usable() {
+        let quote = |close, adjclose| yahoo::Quote {
+            timestamp: 0,
+            open: 1.0,
+            high: 1.0,
+            low: 1.0,
+            volume: 0,
+            close,
+            adjclose,
+        };
+        let adjusted = |close, adjclose| is_usable(&quote(close, adjclose), PriceField::AdjClose);
+        assert!(adjusted(1.0, 0.9));
+        assert!(!adjusted(1.0, f64::NAN));
+        assert!(!adjusted(1.0, f64::INFINITY));
+        assert!(!adjusted(1.0, 0.0));
+        assert!(!adjusted(f64::NAN, 0.9));
+        assert!(!adjusted(0.0, 0.9));
+        assert!(!adjusted(-1.0, 0.9));
+
+        let unadjusted = |close, adjclose| is_usable(&quote(close, adjclose), PriceField::Close);
+        assert!(unadjusted(1.0, 0.9));
+        // the adjusted close isn't needed
+        assert!(unadjusted(1.0, 0.0));
+        assert!(unadjusted(1.0, f64::NAN));
+        assert!(!unadjusted(0.0, 0.9));
+        assert!(!unadjusted(-1.0, 0.9));
+        assert!(!unadjusted(f64::INFINITY, 0.9));
+    }
+
+    #[test]
+    fn test_dedup_timestamps() {
+        let quote = |timestamp, close| yahoo::Quote {
+            timestamp,
+            open: close,
+            high: close,
+            low: close,
+            volume: 0,
+            close,
+            adjclose: close,
+        };
+        let mut quotes = vec![
+            quote(1, 1.0),
+            quote(2, 2.0),
+            quote(2, 2.5),
+            quote(2, 2.6),
+            quote(3, 3.0),
+            quote(4, 4.0),
+            quote(4, 4.5),
+        ];
+        dedup_timestamps(&mut quotes);
+        let closes: Vec<(u64, f64)> = quotes.iter().map(|q| (q.timestamp, q.close)).collect();
+        assert_eq!(closes, vec![(1, 1.0), (2, 2.6), (3, 3.0), (4, 4.5)]);
+
+        let mut quotes = vec![];
+        dedup_timestamps(&mut quotes);
+        assert!(quotes.is_empty());
+    }
+
+    #[test]
+    fn test_Interval_from_str() {
+        assert_eq!("weekly".parse(), Ok(Interval::Weekly));
+        assert!("hourly".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn test_Bar_from_str() {
+        assert_eq!("5m".parse(), Ok(Bar::Minute5));
+        assert_eq!("60m".parse(), Ok(Bar::Hour1));
+        assert_eq!("1h".parse::<Bar>().unwrap().to_string(), "1h");
+        assert!("2m".parse::<Bar>().is_err());
+    }
+
+    #[test]
+    fn test_check_range() {
+        let now = Utc.with_ymd_and_hms(2021, 3, 31, 12, 0, 0).unwrap();
+        let from = Utc.with_ymd_and_hms(2021, 2, 1, 12, 0, 0).unwrap();
+        assert!(check_range(Bar::Day1, &from, &now).is_ok());
+        assert!(check_range(Bar::Minute5, &from, &now).is_ok());
+        let error = check_range(Bar::Minute1, &from, &now).unwrap_err();
+        assert!(matches!(error, FetchError::OutOfRange { days: 30, .. }));
+        assert_eq!(
+            error.to_string(),
+            "1m bars are only available for the last 30 days"
+        );
+        let from = Utc.with_ymd_and_hms(2019, 3, 31, 12, 0, 0).unwrap();
+        assert!(check_range(Bar::Hour1, &from, &now).is_err());
+    }
+}
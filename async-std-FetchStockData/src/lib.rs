@@ -0,0 +1,16 @@
+//!
+//! Fetch stock prices and calculate common trading signals over them.
+//!
+//! The `signals` module provides the calculations, e.g. `PriceDifference {}.calculate(&closes)`,
+//! working on plain closing price series that the `data` module retrieves from a data source, or
+//! `local` from files, optionally through the file-based `cache` and at a steady pace using
+//! `limit`. The `analysis` module calculates the signals shown for a symbol concurrently, and
+//! `metrics` counts the fetches for monitoring.
+//!
+pub mod analysis;
+pub mod cache;
+pub mod data;
+pub mod limit;
+pub mod local;
+pub mod metrics;
+pub mod signals;
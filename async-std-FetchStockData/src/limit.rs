@@ -0,0 +1,156 @@
+//!
+//! Rate limiting and timeouts of requests to a data source.
+//!
+use crate::data::{FetchError, Quote, StockDataProvider};
+use async_std::{future, task};
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Hands out slots at least `min_interval` apart, shared by all concurrent callers.
+pub struct RateLimiter {
+    min_interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(min_interval: Duration) -> Self {
+        RateLimiter {
+            min_interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    ///
+    /// Wait for the next free slot. Slots are reserved in the order of the calls, so a burst of
+    /// callers is spread out evenly instead of retrying for the same slot.
+    ///
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.min_interval;
+            slot
+        };
+        let wait = slot.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            log::debug!("rate limited, waiting {:?}", wait);
+            task::sleep(wait).await;
+        }
+    }
+}
+
+/// Wraps another provider and waits for a `RateLimiter` slot before every request.
+pub struct RateLimitedProvider {
+    pub inner: Arc<dyn StockDataProvider>,
+    pub limiter: RateLimiter,
+}
+
+#[async_trait]
+impl StockDataProvider for RateLimitedProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        self.limiter.acquire().await;
+        self.inner.fetch_quotes(symbol, from, to).await
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        self.limiter.acquire().await;
+        self.inner.fetch_currency(symbol).await
+    }
+}
+
+/// Wraps another provider and gives up on requests, including their retries, after `timeout`.
+pub struct TimeoutProvider {
+    pub inner: Arc<dyn StockDataProvider>,
+    pub timeout: Duration,
+}
+
+#[async_trait]
+impl StockDataProvider for TimeoutProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        future::timeout(self.timeout, self.inner.fetch_quotes(symbol, from, to))
+            .await
+            .unwrap_or(Err(FetchError::Timeout(self.timeout)))
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        future::timeout(self.timeout, self.inner.fetch_currency(symbol))
+            .await
+            .unwrap_or(Err(FetchError::Timeout(self.timeout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    #[async_std::test]
+    async fn test_RateLimiter_acquire() {
+        let limiter = RateLimiter::new(Duration::from_millis(50));
+        let start = Instant::now();
+        futures::future::join_all((0..4).map(|_| limiter.acquire())).await;
+        // the first slot is free, the other three wait for theirs
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        let limiter = RateLimiter::new(Duration::ZERO);
+        let start = Instant::now();
+        futures::future::join_all((0..4).map(|_| limiter.acquire())).await;
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    struct SlowProvider {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl StockDataProvider for SlowProvider {
+        async fn fetch_quotes(
+            &self,
+            _symbol: &str,
+            _from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            task::sleep(self.delay).await;
+            Ok(vec![])
+        }
+    }
+
+    #[async_std::test]
+    async fn test_TimeoutProvider_fetch_quotes() {
+        let now = Utc::now();
+        let provider = TimeoutProvider {
+            inner: Arc::new(SlowProvider {
+                delay: Duration::from_secs(10),
+            }),
+            timeout: Duration::from_millis(50),
+        };
+        let start = Instant::now();
+        let result = provider.fetch_quotes("AAPL", &now, &now).await;
+        assert!(matches!(result, Err(FetchError::Timeout(t)) if t == Duration::from_millis(50)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(result.unwrap_err().to_string(), "no answer within 50ms");
+
+        let provider = TimeoutProvider {
+            inner: Arc::new(SlowProvider {
+                delay: Duration::from_millis(10),
+            }),
+            timeout: Duration::from_secs(10),
+        };
+        assert_eq!(
+            provider.fetch_quotes("AAPL", &now, &now).await.unwrap(),
+            vec![]
+        );
+    }
+}
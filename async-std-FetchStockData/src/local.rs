@@ -0,0 +1,172 @@
+//!
+//! Closing prices from local files, for working offline and deterministic tests.
+//!
+use crate::data::{FetchError, Quote, StockDataProvider};
+use async_std::fs;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+/// Reads the quotes of a symbol from `SYMBOL.csv` in `dir`, e.g. as downloaded from Yahoo! finance.
+/// The files need a `Date` and an `AdjClose` (or `Adj Close`) column, `High`, `Low`, `Volume` and
+/// the unadjusted `Close` are used if present.
+pub struct CsvFileProvider {
+    pub dir: PathBuf,
+}
+
+///
+/// Parse a date like 2021-01-04 or an RFC3339 timestamp.
+///
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Some(date.and_hms_opt(0, 0, 0)?.and_utc()),
+        Err(_) => value.parse().ok(),
+    }
+}
+
+///
+/// Parse the quotes of a CSV file, sorted by time.
+///
+fn parse_csv(content: &str) -> Result<Vec<Quote>, Error> {
+    let invalid = |msg: String| Error::new(ErrorKind::InvalidData, msg);
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(h));
+    let date = column(&["Date"]).ok_or_else(|| invalid("no 'Date' column".to_string()))?;
+    let adjclose = column(&["AdjClose", "Adj Close"])
+        .ok_or_else(|| invalid("no 'AdjClose' column".to_string()))?;
+    let (high, low, volume) = (column(&["High"]), column(&["Low"]), column(&["Volume"]));
+    let raw_close = column(&["Close"]);
+
+    let mut quotes = vec![];
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let row = i + 2;
+        let field = |column: usize| {
+            fields
+                .get(column)
+                .copied()
+                .ok_or_else(|| invalid(format!("line {}: missing column {}", row, column + 1)))
+        };
+        let price = |column: usize| -> Result<f64, Error> {
+            field(column)?
+                .parse()
+                .map_err(|_| invalid(format!("line {}: invalid price", row)))
+        };
+        let timestamp = parse_timestamp(field(date)?)
+            .ok_or_else(|| invalid(format!("line {}: invalid date", row)))?;
+        let close = price(adjclose)?;
+        quotes.push(Quote {
+            timestamp,
+            high: high.map_or(Ok(close), price)?,
+            low: low.map_or(Ok(close), price)?,
+            close,
+            volume: match volume {
+                Some(column) => field(column)?
+                    .parse()
+                    .map_err(|_| invalid(format!("line {}: invalid volume", row)))?,
+                None => 0,
+            },
+            adjustment: match raw_close.map(price).transpose()? {
+                Some(raw) if raw != 0.0 => Some(close / raw),
+                _ => None,
+            },
+        });
+    }
+    quotes.sort_by_key(|q| q.timestamp);
+    Ok(quotes)
+}
+
+#[async_trait]
+impl StockDataProvider for CsvFileProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        let path = self.dir.join(format!("{}.csv", symbol));
+        let content = match fs::read_to_string(&path).await {
+            Ok(content) => content,
+            // like an unknown ticker online
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(FetchError::NoData),
+            Err(e) => return Err(FetchError::File(e)),
+        };
+        let quotes = parse_csv(&content).map_err(FetchError::File)?;
+        // files have days rather than exact times, so whole days are compared
+        let (from, to) = (from.date_naive(), to.date_naive());
+        Ok(quotes
+            .into_iter()
+            .filter(|q| (from..=to).contains(&q.timestamp.date_naive()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let quotes = parse_csv("Date,AdjClose\n2021-01-05,2.5\n2021-01-04,2.0\n\n").unwrap();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(
+            quotes[0].timestamp,
+            Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            (quotes[0].close, quotes[0].high, quotes[0].volume),
+            (2.0, 2.0, 0)
+        );
+        assert_eq!(quotes[0].adjustment, None);
+
+        let yahoo = "Date,Open,High,Low,Close,Adj Close,Volume\n\
+                     2021-01-04,133.5,133.6,126.7,129.4,128.6,143301900\n";
+        let quotes = parse_csv(yahoo).unwrap();
+        assert_eq!((quotes[0].high, quotes[0].low), (133.6, 126.7));
+        assert_eq!((quotes[0].close, quotes[0].volume), (128.6, 143301900));
+        assert_eq!(quotes[0].adjustment, Some(128.6 / 129.4));
+
+        assert!(parse_csv("Date,Close\n2021-01-04,1.0").is_err());
+        let err = parse_csv("Date,AdjClose\n2021-01-04,1.0\n2021-01-05,x").unwrap_err();
+        assert!(err.to_string().contains("line 3"));
+        assert!(parse_csv("Date,AdjClose\nyesterday,1.0").is_err());
+        assert!(parse_csv("").is_err());
+    }
+
+    #[async_std::test]
+    async fn test_CsvFileProvider_fetch_quotes() {
+        let dir = std::env::temp_dir().join("test_CsvFileProvider_fetch_quotes");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("AAPL.csv"),
+            "Date,AdjClose\n2021-01-04,1.0\n2021-01-05,2.0\n2021-01-06,3.0\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("BROKEN.csv"), "Date\n2021-01-04\n").unwrap();
+        let provider = CsvFileProvider { dir: dir.clone() };
+        let from = Utc.with_ymd_and_hms(2021, 1, 5, 12, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap();
+
+        let quotes = provider.fetch_quotes("AAPL", &from, &to).await.unwrap();
+        let closes: Vec<f64> = quotes.iter().map(|q| q.close).collect();
+        assert_eq!(closes, vec![2.0, 3.0]);
+        assert!(matches!(
+            provider.fetch_quotes("MSFT", &from, &to).await,
+            Err(FetchError::NoData)
+        ));
+        assert!(matches!(
+            provider.fetch_quotes("BROKEN", &from, &to).await,
+            Err(FetchError::File(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,149 +1,1862 @@
 use chrono::prelude::*;
-use clap::Clap;
-use std::io::{Error, ErrorKind};
+use chrono_tz::Tz;
+use clap::{Parser, ValueEnum};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, IsTerminal, Write};
 use colored::*;
-use yahoo_finance_api as yahoo;
-use async_trait::async_trait;
+use async_std::channel::{self, Receiver, Sender};
+use async_std::task;
+use futures::future::{self, Either};
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use manning_lp_async_rust_project_1_m1::analysis::{
+    aligned_closes, beta, compute_all, correlation, portfolio_index, relative_change, EMA_PERIOD,
+};
+use manning_lp_async_rust_project_1_m1::cache::{CachedProvider, CurrencyMemo};
+use manning_lp_async_rust_project_1_m1::data::{
+    adjustment_jumps, downsample, forward_fill, price_jumps, Bar, FetchError, Interval, PriceField,
+    Quote, Session, StockDataProvider, Transient, YahooProvider,
+};
+use manning_lp_async_rust_project_1_m1::limit::{
+    RateLimitedProvider, RateLimiter, TimeoutProvider,
+};
+use manning_lp_async_rust_project_1_m1::local::CsvFileProvider;
+use manning_lp_async_rust_project_1_m1::metrics::{MeteredProvider, Metrics};
+use manning_lp_async_rust_project_1_m1::signals::{
+    simple_returns, AverageVolume, ExponentialMovingAverage, PriceDifference, Rsi, SignalInput,
+    StockSignal, Volatility, WindowedSMA, SIGNALS,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Clap)]
+#[derive(Parser)]
 #[clap(
     version = "0.1",
     author = "Stephen Mizon",
-    about = "A Manning LiveProject: async Rust"
+    about = "A Manning LiveProject: async Rust",
+    args_override_self = true
 )]
 struct Opts {
-    #[clap(short, long, default_value = "AAPL,MSFT,UBER,GOOG")]
-    symbols: String,
+    /// TOML file with defaults for any of these options, e.g. `sma_window = 10`.
+    /// Options on the command line take precedence over the file, which takes precedence
+    /// over the built-in defaults
+    #[clap(long)]
+    config: Option<String>,
+    /// Symbols separated by commas, semicolons or whitespace, defaults to AAPL,MSFT,UBER,GOOG without a symbols file
+    #[clap(short, long)]
+    symbols: Option<String>,
+    /// File with symbols separated like in --symbols, e.g. one per line, merged with --symbols
+    #[clap(long)]
+    symbols_file: Option<String>,
+    /// Start of the period, a date like 2020-07-03 for midnight UTC or an RFC3339 timestamp
+    #[clap(short, long, required_unless_present_any = &["serve", "since", "list-signals"])]
+    from: Option<String>,
+    /// Start the period this long before its end instead of --from, e.g. 7d, 3w, 6mo or 1y
+    #[clap(long, conflicts_with = "from")]
+    since: Option<String>,
+    /// Also fetch the equally long period right before and show its change next to this one's,
+    /// with the difference in percentage points
+    #[clap(long, alias = "compare-periods")]
+    compare_prior: bool,
+    /// End of the period like --from, defaults to now
+    #[clap(short, long)]
+    to: Option<String>,
+    #[clap(long, value_enum, default_value = "csv")]
+    output_format: OutputFormat,
+    /// Indent the JSON output for reading, JSON lines stay compact
+    #[clap(long, alias = "pretty-json")]
+    pretty: bool,
+    /// Separate the CSV fields with this character instead, defaults to a comma or a tab for TSV
+    #[clap(long)]
+    delimiter: Option<char>,
+    /// Number of days in the simple moving average window, at least 2
+    #[clap(long, default_value_t = 30)]
+    sma_window: usize,
+    /// Skip symbols with fewer data points than this, with a warning, defaults to the longest
+    /// average's window, 0 skips none
+    #[clap(long)]
+    min_points: Option<usize>,
+    /// Number of retries for transient download errors
+    #[clap(long, default_value_t = 3)]
+    max_retries: u32,
+    /// Only retry these comma-separated kinds of errors: network (including server errors) and
+    /// rate-limit (HTTP 429). Others, like an unknown symbol, fail right away
+    #[clap(long, value_delimiter = ',', default_value = "network,rate-limit")]
+    retries_only_on: Vec<Transient>,
+    /// Give up on a symbol whose quotes didn't arrive within this many seconds, retries
+    /// included, 0 waits forever
+    #[clap(long, default_value_t = 30)]
+    timeout_secs: u64,
+    /// Request bars of this size: 1m, 5m, 15m, 30m, 1h or 1d. Intraday bars only go back
+    /// 30 (1m), 60 (up to 30m) or 730 (1h) days
+    #[clap(long, default_value = "1d", conflicts_with_all = &["from-csv", "fill"])]
+    bar: Bar,
+    /// Only keep the intraday bars of the regular session, 9:30 to 16:00 New York time as for
+    /// US equities, leaving out pre-market and after-hours trading
+    #[clap(long, alias = "market-hours-only")]
+    regular_session_only: bool,
+    /// The closing prices to use: adjclose, adjusted for splits and dividends so returns over
+    /// them are right, or close, as traded then, e.g. to match a broker statement
+    #[clap(long, default_value = "adjclose", conflicts_with = "from-csv")]
+    price_field: PriceField,
+    /// Warn about unadjusted closes that moved more than PCT percent against the adjusted ones
+    /// from the previous day, 40 if not given, which points to a split or dividend. Sources
+    /// without both closes are checked for such moves of the close
+    #[clap(long, value_name = "PCT", min_values = 0, default_missing_value = "40")]
+    warn_on_jumps: Option<f64>,
+    /// Aggregate the daily closes to: daily, weekly or monthly
+    #[clap(long, default_value = "daily")]
+    interval: Interval,
+    /// Add a column with the change relative to this symbol, e.g. an index like SPY
+    #[clap(long, conflicts_with = "serve")]
+    benchmark: Option<String>,
+    /// Also store the reports of every run in the `signals` table of this SQLite database
+    #[clap(long)]
+    sqlite: Option<String>,
+    /// Only show symbols, and the PORTFOLIO row, whose change exceeds this many percent up or
+    /// down, highlighting them.
+    /// Exits with 3 if any such alert fired and no symbol failed
+    #[clap(long)]
+    alert_threshold: Option<f64>,
+    /// Show timestamps in this time zone, e.g. America/New_York
+    #[clap(long, default_value = "UTC")]
+    timezone: Tz,
+    /// Number of decimals for prices and percentages in CSV output, defaults to 2 or to none
+    /// for prices in currencies without cents, like JPY
+    #[clap(long)]
+    precision: Option<usize>,
+    /// Show prices in this currency, e.g. EUR, instead of the one the data source reports or
+    /// dollars if it doesn't
+    #[clap(long)]
+    currency: Option<String>,
+    /// Forward-fill the prices onto every calendar day up to --to, repeating the last close
+    /// over weekends, holidays and gaps. Filled days aren't real trades and have no volume
+    #[clap(long, alias = "interval-fill")]
+    fill: bool,
+    /// Drop the first this many values of the SMA, EMA and other series while they settle,
+    /// which shortens them
+    #[clap(long, default_value_t = 0)]
+    warmup: usize,
+    /// Add a column with the average volume traded per interval
+    #[clap(long)]
+    with_volume: bool,
+    /// Add a column with the volatility of the returns, annualized with --trading-days
+    #[clap(long)]
+    with_volatility: bool,
+    /// Trading days per year for annualizing, e.g. 252 for equities or 365 for crypto
+    #[clap(long, default_value_t = 252)]
+    trading_days: usize,
+    /// Write the data to this file instead of stdout
     #[clap(short, long)]
+    output: Option<String>,
+    /// Append to the --output file instead of overwriting it, the header only goes into a new or
+    /// empty file
+    #[clap(long, requires = "output")]
+    append: bool,
+    /// Leave out the CSV header line, e.g. for chunks that go into one file
+    #[clap(long)]
+    no_header: bool,
+    /// Skip the banner and any coloring, only print the data
+    #[clap(short, long)]
+    quiet: bool,
+    /// Re-fetch every this many seconds until interrupted, the period ends now each time
+    #[clap(long, conflicts_with = "to")]
+    poll: Option<u64>,
+    /// Serve the signals as JSON on this port instead, at
+    /// /signals?symbols=AAPL,MSFT&from=2020-07-03T12:00:09Z&to=...
+    #[clap(long, conflicts_with_all = &["poll", "output"])]
+    serve: Option<u16>,
+    /// Serve Prometheus metrics at /metrics on this port while polling, --serve always has them
+    #[clap(long, requires = "poll")]
+    metrics_port: Option<u16>,
+    /// Print the closes of only this symbol with the full SMA, EMA and RSI series next to them
+    /// instead, to check the math
+    #[clap(long, conflicts_with_all = &["poll", "serve", "top"])]
+    dump: Option<String>,
+    /// Print the correlations of the returns of all pairs of these symbols instead, as a matrix
+    #[clap(long, conflicts_with_all = &["poll", "serve", "top", "dump"])]
+    correlate: Option<String>,
+    /// Add a PORTFOLIO row for holding all symbols, as an index starting at 100 on the first
+    /// day they all have quotes for
+    #[clap(long, conflicts_with_all = &["poll", "serve", "top", "dump", "correlate"])]
+    portfolio: bool,
+    /// The weights of the symbols in the portfolio at the start, in their order, by default
+    /// equal
+    #[clap(long, requires = "portfolio", value_delimiter = ',')]
+    weights: Option<Vec<f64>>,
+    /// Directory for cached downloads, defaults to a directory in the system's temp dir
+    #[clap(long)]
+    cache_dir: Option<String>,
+    /// Seconds that cached downloads stay valid
+    #[clap(long, default_value_t = 3600)]
+    cache_ttl: u64,
+    /// Minimum number of milliseconds between two downloads, even when fetching concurrently
+    #[clap(long, default_value_t = 0)]
+    rate_limit_ms: u64,
+    /// Always download, neither reading nor writing the cache
+    #[clap(long)]
+    no_cache: bool,
+    /// Read the quotes from SYMBOL.csv files in this directory instead of downloading them,
+    /// with a Date and an AdjClose column
+    #[clap(long)]
+    from_csv: Option<String>,
+    /// Maximum number of symbols fetched at the same time
+    #[clap(long, default_value_t = 8)]
+    concurrency: usize,
+    /// Keep the order of the symbols instead of writing each as soon as it's done
+    #[clap(long)]
+    sorted: bool,
+    /// Stop at the first symbol that fails, leaving out the ones not done yet, and exit with 1
+    #[clap(long, overrides_with = "keep-going", conflicts_with_all = &["poll", "serve"])]
+    fail_fast: bool,
+    /// Go on with the other symbols when one fails and list the failures at the end, the
+    /// default, e.g. to override --fail-fast from a config file
+    #[clap(long, overrides_with = "fail-fast")]
+    keep_going: bool,
+    /// Only output these comma-separated fields, in this order, e.g. symbol,last_price,change
+    #[clap(long, value_enum, value_delimiter = ',')]
+    columns: Option<Vec<Column>>,
+    /// Only show the N symbols ranking highest by --sort-by, after all are done
+    #[clap(long, conflicts_with_all = &["poll", "serve"])]
+    top: Option<usize>,
+    /// The metric --top ranks by, in descending order
+    #[clap(long, value_enum, requires = "top")]
+    sort_by: Option<SortBy>,
+    /// Print the signals the library calculates with their parameters and exit
+    #[clap(long)]
+    list_signals: bool,
+    /// Disable colors, also done by setting NO_COLOR or redirecting stdout
+    #[clap(long)]
+    no_color: bool,
+    /// Show a progress bar of the fetches on stderr, if it's a terminal
+    #[clap(long)]
+    progress: bool,
+    /// Color the change in CSV output green for gains and red for losses, bold for big moves
+    #[clap(long, alias = "highlight-gains")]
+    color_moves: bool,
+}
+
+/// The metrics symbols can be ranked by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SortBy {
+    Change,
+    Min,
+    Max,
+    Volatility,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Csv,
+    Tsv,
+    Json,
+    /// A compact JSON object per line, as soon as each report is done
+    #[clap(alias = "json-lines")]
+    Jsonl,
+    /// Aligned columns under a header rule for reading in a terminal, once all reports are done
+    Table,
+}
+
+/// How the reports are written, see the output options.
+struct Layout {
+    format: OutputFormat,
+    /// Decimals of prices and percentages in CSV output, if not the currency's
+    precision: Option<usize>,
+    /// Separates the fields of CSV output
+    delimiter: char,
+    columns: Vec<Column>,
+    sma_window: usize,
+    /// The bar size and interval of the quotes, the units of the averages' periods
+    bar: Bar,
+    interval: Interval,
+    /// Whether the change is colored by its direction
+    color_moves: bool,
+    /// Whether a JSON array is indented
+    pretty: bool,
+}
+
+///
+/// Serialize a series of averages as its latest value, or null for an empty series.
+///
+fn serialize_latest<S: serde::Serializer>(
+    series: &[f64],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    series.last().serialize(serializer)
+}
+
+/// The signals calculated for a single symbol over the requested period.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct Report {
+    /// When the data was fetched, only set when polling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    period_start: String,
+    symbol: String,
+    last_price: f64,
+    change_pct: f64,
+    min: f64,
+    max: f64,
+    /// Serialized as the latest average, null if there's none
+    #[serde(serialize_with = "serialize_latest")]
+    sma: Vec<f64>,
+    #[serde(serialize_with = "serialize_latest")]
+    ema: Vec<f64>,
+    /// The average volume per interval, only set with `--with-volume`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<f64>,
+    /// The change minus the benchmark's in percent, only set with `--benchmark`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_change_pct: Option<f64>,
+    /// The change over the equally long period before in percent, only set with
+    /// `--compare-prior`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prior_change_pct: Option<f64>,
+    /// The change minus the prior one in percentage points, only set with `--compare-prior`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    change_vs_prior_pct: Option<f64>,
+    /// The beta of the returns to the benchmark's, only set with `--benchmark`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    beta: Option<f64>,
+    /// The annualized standard deviation of the returns in percent, only set with
+    /// `--with-volatility` or when ranking by it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volatility_pct: Option<f64>,
+    /// The currency of the prices, e.g. "EUR", if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    /// The quotes the signals were calculated from, for the portfolio
+    #[serde(skip)]
+    quotes: Vec<Quote>,
+}
+
+impl Report {
+    /// Format the columns of the report as a CSV row with `precision` decimals for prices and
+    /// percentages, by default the currency's for prices and 2 for percentages.
+    fn to_csv(&self, precision: Option<usize>, columns: &[Column], delimiter: char) -> String {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| column.csv_cell(self, precision))
+            .collect();
+        csv_row(&cells, delimiter)
+    }
+
+    /// The CSV row of the report in the layout, see `to_csv`, with the change colored if asked.
+    fn to_row(&self, layout: &Layout) -> String {
+        if !layout.color_moves {
+            return self.to_csv(layout.precision, &layout.columns, layout.delimiter);
+        }
+        let cells: Vec<String> = layout
+            .columns
+            .iter()
+            .map(|column| {
+                let cell = column.csv_cell(self, layout.precision);
+                match column {
+                    Column::Change => color_move(&cell, self.change_pct),
+                    _ => cell,
+                }
+            })
+            .collect();
+        csv_row(&cells, layout.delimiter)
+    }
+
+    /// The columns of the report as a JSON object, in the same order.
+    fn to_json(&self, columns: &[Column]) -> serde_json::Value {
+        columns
+            .iter()
+            .map(|column| (column.json_key().to_string(), column.json_value(self)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+///
+/// How prices in the currency are prefixed, e.g. with $ for USD or "CHF " without a common
+/// symbol. Prices in an unknown currency are shown as dollars.
+///
+fn currency_symbol(currency: Option<&str>) -> String {
+    match currency {
+        None | Some("USD") => "$".to_string(),
+        Some("EUR") => "€".to_string(),
+        Some("GBP") => "£".to_string(),
+        Some("JPY") => "¥".to_string(),
+        Some("INR") => "₹".to_string(),
+        Some("KRW") => "₩".to_string(),
+        Some(code) => format!("{} ", code),
+    }
+}
+
+///
+/// The usual number of decimals of prices in the currency, none for currencies without a minor
+/// unit that's still in use, like JPY.
+///
+fn currency_decimals(currency: Option<&str>) -> usize {
+    match currency {
+        Some("JPY" | "KRW" | "CLP" | "ISK" | "VND") => 0,
+        _ => 2,
+    }
+}
+
+/// A change of at least this many percent either way is a big move.
+const BIG_MOVE_PCT: f64 = 5.0;
+
+///
+/// Style a change cell green for gains and red for losses, bold for big moves. Without colors,
+/// e.g. when piped, the cell stays as it is.
+///
+fn color_move(cell: &str, change_pct: f64) -> String {
+    let styled = if change_pct > 0.0 {
+        cell.green()
+    } else if change_pct < 0.0 {
+        cell.red()
+    } else {
+        cell.normal()
+    };
+    if change_pct.abs() >= BIG_MOVE_PCT {
+        styled.bold().to_string()
+    } else {
+        styled.to_string()
+    }
+}
+
+/// The fields of a report that can be output, see `--columns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[clap(rename_all = "snake_case")]
+enum Column {
+    Timestamp,
+    PeriodStart,
+    Symbol,
+    LastPrice,
+    Change,
+    PriorChange,
+    ChangeVsPrior,
+    Min,
+    Max,
+    Sma,
+    Ema,
+    Volume,
+    RelativeChange,
+    Beta,
+    Volatility,
+}
+
+impl Column {
+    /// Whether the column shows prices, which are formatted in their currency.
+    fn is_price(self) -> bool {
+        matches!(
+            self,
+            Column::LastPrice | Column::Min | Column::Max | Column::Sma | Column::Ema
+        )
+    }
+
+    /// The CSV header of the column.
+    fn header(self, layout: &Layout) -> String {
+        match self {
+            Column::Timestamp => "timestamp".to_string(),
+            Column::PeriodStart => "period start".to_string(),
+            Column::Symbol => "symbol".to_string(),
+            Column::LastPrice => "price".to_string(),
+            Column::Change => "change %".to_string(),
+            Column::PriorChange => "prior change %".to_string(),
+            Column::ChangeVsPrior => "vs prior".to_string(),
+            Column::Min => "min".to_string(),
+            Column::Max => "max".to_string(),
+            Column::Sma => format!(
+                "{} avg",
+                periods_label(layout.sma_window, layout.bar, layout.interval)
+            ),
+            Column::Ema => format!(
+                "{} ema",
+                periods_label(EMA_PERIOD, layout.bar, layout.interval)
+            ),
+            Column::Volume => "volume".to_string(),
+            Column::RelativeChange => "relative change %".to_string(),
+            Column::Beta => "beta".to_string(),
+            Column::Volatility => "annualized volatility %".to_string(),
+        }
+    }
+
+    /// The CSV cell of a report, the last values are used for the averages and
+    /// missing values are left blank rather than shown as zero, which could be a price.
+    fn csv_cell(self, report: &Report, precision: Option<usize>) -> String {
+        let currency = report.currency.as_deref();
+        let decimals = precision.unwrap_or_else(|| currency_decimals(currency));
+        let symbol = currency_symbol(currency);
+        let price = |price: f64| format!("{}{:.p$}", symbol, price, p = decimals);
+        let percent = |percent: Option<f64>| match percent {
+            Some(percent) => format!("{:.p$}%", percent, p = precision.unwrap_or(2)),
+            None => String::new(),
+        };
+        match self {
+            Column::Timestamp => report.timestamp.clone().unwrap_or_default(),
+            Column::PeriodStart => report.period_start.clone(),
+            Column::Symbol => report.symbol.clone(),
+            Column::LastPrice => price(report.last_price),
+            Column::Change => percent(Some(report.change_pct)),
+            Column::PriorChange => percent(report.prior_change_pct),
+            Column::ChangeVsPrior => report
+                .change_vs_prior_pct
+                .map(|delta| format!("{:+.p$}", delta, p = precision.unwrap_or(2)))
+                .unwrap_or_default(),
+            Column::Min => price(report.min),
+            Column::Max => price(report.max),
+            Column::Sma => report.sma.last().map(|sma| price(*sma)).unwrap_or_default(),
+            Column::Ema => report.ema.last().map(|ema| price(*ema)).unwrap_or_default(),
+            Column::Volume => report
+                .volume
+                .map(|volume| format!("{:.0}", volume))
+                .unwrap_or_default(),
+            Column::RelativeChange => percent(report.relative_change_pct),
+            Column::Beta => report
+                .beta
+                .map(|beta| format!("{:.p$}", beta, p = precision.unwrap_or(2)))
+                .unwrap_or_default(),
+            Column::Volatility => percent(report.volatility_pct),
+        }
+    }
+
+    /// The JSON key of the column, the same as in the serialized report.
+    fn json_key(self) -> &'static str {
+        match self {
+            Column::Timestamp => "timestamp",
+            Column::PeriodStart => "period_start",
+            Column::Symbol => "symbol",
+            Column::LastPrice => "last_price",
+            Column::Change => "change_pct",
+            Column::PriorChange => "prior_change_pct",
+            Column::ChangeVsPrior => "change_vs_prior_pct",
+            Column::Min => "min",
+            Column::Max => "max",
+            Column::Sma => "sma",
+            Column::Ema => "ema",
+            Column::Volume => "volume",
+            Column::RelativeChange => "relative_change_pct",
+            Column::Beta => "beta",
+            Column::Volatility => "volatility_pct",
+        }
+    }
+
+    /// The JSON value of a report, with the averages as their full series or null if the
+    /// period was too short for any average.
+    fn json_value(self, report: &Report) -> serde_json::Value {
+        use serde_json::json;
+        match self {
+            Column::Timestamp => json!(report.timestamp),
+            Column::PeriodStart => json!(report.period_start),
+            Column::Symbol => json!(report.symbol),
+            Column::LastPrice => json!(report.last_price),
+            Column::Change => json!(report.change_pct),
+            Column::PriorChange => json!(report.prior_change_pct),
+            Column::ChangeVsPrior => json!(report.change_vs_prior_pct),
+            Column::Min => json!(report.min),
+            Column::Max => json!(report.max),
+            Column::Sma => json!((!report.sma.is_empty()).then_some(&report.sma)),
+            Column::Ema => json!((!report.ema.is_empty()).then_some(&report.ema)),
+            Column::Volume => json!(report.volume),
+            Column::RelativeChange => json!(report.relative_change_pct),
+            Column::Beta => json!(report.beta),
+            Column::Volatility => json!(report.volatility_pct),
+        }
+    }
+}
+
+///
+/// The columns shown without `--columns`: all that have values with the given options.
+///
+fn default_columns(
+    timestamped: bool,
+    with_volume: bool,
+    with_benchmark: bool,
+    with_volatility: bool,
+) -> Vec<Column> {
+    let mut columns = vec![];
+    if timestamped {
+        columns.push(Column::Timestamp);
+    }
+    columns.extend([
+        Column::PeriodStart,
+        Column::Symbol,
+        Column::LastPrice,
+        Column::Change,
+        Column::Min,
+        Column::Max,
+        Column::Sma,
+        Column::Ema,
+    ]);
+    for (enabled, column) in [
+        (with_volume, Column::Volume),
+        (with_benchmark, Column::RelativeChange),
+        (with_benchmark, Column::Beta),
+        (with_volatility, Column::Volatility),
+    ] {
+        if enabled {
+            columns.push(column);
+        }
+    }
+    columns
+}
+
+///
+/// Calculate all signals over a series of closing prices, with the period start shown in the
+/// time zone of `from`. Returns None if there are no closing prices to work with.
+///
+async fn process<Z: TimeZone>(
+    symbol: &str,
+    from: &DateTime<Z>,
+    closes: &[f64],
+    sma_window: usize,
+) -> Option<Report> {
+    let summary = compute_all(closes, sma_window).await?;
+    Some(Report {
+        timestamp: None,
+        period_start: from.to_rfc3339(),
+        symbol: symbol.to_string(),
+        last_price: summary.last_price,
+        change_pct: summary.change_pct,
+        min: summary.min,
+        max: summary.max,
+        sma: summary.sma,
+        ema: summary.ema,
+        volume: None,
+        relative_change_pct: None,
+        prior_change_pct: None,
+        change_vs_prior_pct: None,
+        beta: None,
+        volatility_pct: None,
+        currency: None,
+        quotes: vec![],
+    })
+}
+
+/// How the quotes are fetched and the reports calculated from them.
+#[derive(Clone)]
+struct Settings {
+    sma_window: usize,
+    /// The size of the bars that are fetched
+    bar: Bar,
+    /// The number of data points below which a symbol is skipped, 0 for none
+    min_points: usize,
+    interval: Interval,
+    /// The trading session intraday quotes are restricted to, if any
+    session: Option<Session>,
+    /// The move in percent between consecutive closes that is warned about, if any
+    jump_threshold: Option<f64>,
+    /// Whether the change is compared with the equally long period before
+    compare_prior: bool,
+    /// Whether the quotes are forward-filled to every calendar day
+    fill: bool,
+    /// The number of unsettled values dropped from the start of each series
+    warmup: usize,
+    /// Whether the fetches are shown as a progress bar on stderr
+    progress: bool,
+    with_volume: bool,
+    /// Whether the volatility is calculated, to show or rank by it
+    with_volatility: bool,
+    /// The trading days per year the volatility is annualized with
+    trading_days: usize,
+    /// The downsampled quotes of the benchmark, if any
+    benchmark: Option<Arc<[Quote]>>,
+    /// The maximum number of symbols fetched at the same time
+    concurrency: usize,
+    /// Whether reports are passed on in the order of the symbols
+    sorted: bool,
+    /// Whether the first failed symbol stops the others from being fetched
+    fail_fast: bool,
+    /// Where fetches and prices are recorded for monitoring
+    metrics: Arc<Metrics>,
+    /// The time zone timestamps are shown in
+    timezone: Tz,
+    /// The currency prices are shown in instead of the one the data source reports
+    currency: Option<String>,
+    /// Whether the data source is asked for the currency, only needed to show prices with it
+    with_currency: bool,
+}
+
+///
+/// Fetch the quotes for a symbol, restricted to the session, forward-filled to every day with
+/// `fill`, and downsample them to the interval.
+///
+async fn fetch_downsampled(
+    provider: &dyn StockDataProvider,
+    symbol: &str,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    settings: &Settings,
+) -> Result<Vec<Quote>, FetchError> {
+    debug!("fetching {} from {} to {}", symbol, from, to);
+    let start = Instant::now();
+    let quotes = provider.fetch_quotes(symbol, from, to).await?;
+    info!(
+        "fetched {} quotes for {} in {:?}",
+        quotes.len(),
+        symbol,
+        start.elapsed()
+    );
+    if let Some(threshold) = settings.jump_threshold {
+        if quotes.iter().any(|q| q.adjustment.is_some()) {
+            for (timestamp, pct) in adjustment_jumps(&quotes, threshold) {
+                warn!(
+                    "{}'s unadjusted close moved {:+.1}% against the adjusted one on {}, \
+                     maybe a split or dividend",
+                    symbol,
+                    pct,
+                    timestamp.date_naive()
+                );
+            }
+        } else {
+            // without both closes only the one at hand can be checked
+            for (timestamp, pct) in price_jumps(&quotes, threshold) {
+                warn!(
+                    "{} moved {:+.1}% on {}, maybe a split or bad data",
+                    symbol,
+                    pct,
+                    timestamp.date_naive()
+                );
+            }
+        }
+    }
+    let quotes = match &settings.session {
+        Some(session) => quotes
+            .into_iter()
+            .filter(|q| session.contains(&q.timestamp))
+            .collect(),
+        None => quotes,
+    };
+    let quotes = if settings.fill {
+        forward_fill(&quotes, to)
+    } else {
+        quotes
+    };
+    Ok(downsample(&quotes, settings.interval))
+}
+
+///
+/// Fetch the quotes for a symbol from the provider and calculate its signals,
+/// including the average volume if `with_volume` is set and the change relative to the
+/// benchmark if there is one. A symbol without any quotes fails with `FetchError::NoData`,
+/// like an unknown ticker. One with fewer than the settings' `min_points` is skipped with a
+/// warning on stderr, returning None.
+///
+async fn fetch_and_process(
+    provider: &dyn StockDataProvider,
+    symbol: &str,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    settings: &Settings,
+) -> Result<Option<Report>, FetchError> {
+    let quotes = fetch_downsampled(provider, symbol, from, to, settings).await?;
+    if !quotes.is_empty() && quotes.len() < settings.min_points {
+        warn!(
+            "skipping {}, it has {} data points, fewer than the minimum of {}",
+            symbol,
+            quotes.len(),
+            settings.min_points
+        );
+        return Ok(None);
+    }
+    let closes: Vec<f64> = quotes.iter().map(|q| q.close).collect();
+    let period_start = from.with_timezone(&settings.timezone);
+    let mut report = process(symbol, &period_start, &closes, settings.sma_window)
+        .await
+        .ok_or(FetchError::NoData)?;
+    skip_warmup(&mut report.sma, settings.warmup);
+    skip_warmup(&mut report.ema, settings.warmup);
+    if settings.with_volume {
+        let volumes: Vec<f64> = quotes.iter().map(|q| q.volume as f64).collect();
+        report.volume = AverageVolume {}.calculate(&volumes).await;
+    }
+    if settings.with_volatility {
+        report.volatility_pct = Volatility {
+            trading_days: Some(settings.trading_days),
+        }
+        .calculate(&closes)
+        .await
+        .map(|volatility| volatility * 100.0);
+    }
+    report.currency = match &settings.currency {
+        Some(currency) => Some(currency.clone()),
+        // the prices are worth showing without their currency too
+        None if settings.with_currency => {
+            provider.fetch_currency(symbol).await.unwrap_or_else(|e| {
+                debug!("couldn't fetch the currency of {}: {}", symbol, e);
+                None
+            })
+        }
+        None => None,
+    };
+    settings
+        .metrics
+        .last_price
+        .with_label_values(&[symbol])
+        .set(report.last_price);
+    if let Some(benchmark) = &settings.benchmark {
+        report.relative_change_pct = relative_change(&quotes, benchmark)
+            .await
+            .map(|change| change * 100.0);
+        let (closes, benchmark_closes) = aligned_closes(&quotes, benchmark);
+        report.beta = beta(&simple_returns(&closes), &simple_returns(&benchmark_closes));
+    }
+    if settings.compare_prior {
+        report.prior_change_pct = prior_change(provider, symbol, from, to, settings).await;
+        report.change_vs_prior_pct = report
+            .prior_change_pct
+            .map(|prior| report.change_pct - prior);
+    }
+    report.quotes = quotes;
+    Ok(Some(report))
+}
+
+///
+/// The change in percent over the period of the same length that ends right before `from`.
+/// Returns None if it has no quotes or couldn't be fetched, which is only logged.
+///
+async fn prior_change(
+    provider: &dyn StockDataProvider,
+    symbol: &str,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    settings: &Settings,
+) -> Option<f64> {
+    // the quote on `from` belongs to the current period
+    let prior_to = *from - chrono::Duration::seconds(1);
+    let prior_from = *from - (*to - *from);
+    match fetch_downsampled(provider, symbol, &prior_from, &prior_to, settings).await {
+        Ok(quotes) => {
+            let closes: Vec<f64> = quotes.iter().map(|q| q.close).collect();
+            let (_, change) = PriceDifference {}.calculate(&closes).await?;
+            Some(change * 100.0)
+        }
+        Err(e) => {
+            debug!("couldn't fetch the prior period of {}: {}", symbol, e);
+            None
+        }
+    }
+}
+
+///
+/// The PORTFOLIO report of holding the symbols of the reports with the weights of the symbols,
+/// see `portfolio_index`. Failed symbols are left out and a symbol listed more than once is held
+/// with the sum of its weights. Returns None if there's nothing to hold.
+///
+async fn portfolio_report(
+    reports: &[Report],
+    symbols: &[String],
+    weights: &[f64],
+    from: &DateTime<Utc>,
+    settings: &Settings,
+) -> Option<Report> {
+    let mut held: Vec<&Report> = vec![];
+    for report in reports {
+        if !held.iter().any(|r| r.symbol == report.symbol) {
+            held.push(report);
+        }
+    }
+    let held_weights: Vec<f64> = held
+        .iter()
+        .map(|report| {
+            symbols
+                .iter()
+                .zip(weights)
+                .filter(|(symbol, _)| **symbol == report.symbol)
+                .map(|(_, weight)| weight)
+                .sum()
+        })
+        .collect();
+    let series: Vec<&[Quote]> = held.iter().map(|r| r.quotes.as_slice()).collect();
+    let closes = portfolio_index(&series, &held_weights)?;
+    let period_start = from.with_timezone(&settings.timezone);
+    let mut report = process("PORTFOLIO", &period_start, &closes, settings.sma_window).await?;
+    skip_warmup(&mut report.sma, settings.warmup);
+    skip_warmup(&mut report.ema, settings.warmup);
+    // an index of prices in the same currency is in that currency too
+    let currency = held.first()?.currency.clone();
+    if held.iter().all(|r| r.currency == currency) {
+        report.currency = currency;
+    }
+    Some(report)
+}
+
+///
+/// Drop the first `warmup` values of a series, while an average or the like hasn't settled yet.
+/// Shorter series end up empty.
+///
+fn skip_warmup(series: &mut Vec<f64>, warmup: usize) {
+    series.drain(..warmup.min(series.len()));
+}
+
+///
+/// Add the benchmark's quotes for the period to the settings, if there is a benchmark.
+///
+async fn with_benchmark(
+    settings: &Settings,
+    provider: &dyn StockDataProvider,
+    benchmark: Option<&str>,
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+) -> Result<Settings, FetchError> {
+    let benchmark = match benchmark {
+        Some(symbol) => {
+            let quotes = fetch_downsampled(provider, symbol, from, to, settings).await?;
+            if quotes.is_empty() {
+                return Err(FetchError::NoData);
+            }
+            Some(quotes.into())
+        }
+        None => None,
+    };
+    Ok(Settings {
+        benchmark,
+        ..settings.clone()
+    })
+}
+
+///
+/// Describe why a symbol failed, pointing out likely typos when there's no data.
+///
+fn failure_message(symbol: &str, error: &FetchError) -> String {
+    match error {
+        FetchError::NoData | FetchError::Http(404) => {
+            format!("no data for {} (check the ticker and date range)", symbol)
+        }
+        e => format!("{}: {}", symbol, e),
+    }
+}
+
+const DEFAULT_SYMBOLS: &str = "AAPL,MSFT,UBER,GOOG";
+
+///
+/// Split a list of symbols separated by commas, semicolons or whitespace (including newlines),
+/// as pasted from various sources, into uppercase tickers, skipping blank entries.
+///
+fn split_symbols(raw: &str) -> impl Iterator<Item = String> + '_ {
+    raw.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(str::to_uppercase)
+}
+
+/// Longer symbols are most likely a mistake, Yahoo's are well below it.
+const MAX_SYMBOL_LEN: usize = 15;
+
+///
+/// Split a list of symbols like `split_symbols`, keeping the first occurrence of each.
+/// Symbols that can't be tickers are an error, i.e. longer ones than `MAX_SYMBOL_LEN` or with
+/// other characters than letters, digits and `.-^=` as in BRK.B, BTC-USD, ^GSPC or CL=F.
+///
+fn parse_symbols(raw: &str) -> Result<Vec<String>, String> {
+    let is_ticker_char = |c: char| c.is_ascii_alphanumeric() || ".-^=".contains(c);
+    let mut symbols: Vec<String> = vec![];
+    for symbol in split_symbols(raw) {
+        if symbol.len() > MAX_SYMBOL_LEN || !symbol.chars().all(is_ticker_char) {
+            return Err(format!(
+                "Invalid symbol '{}', expected a ticker like AAPL, BRK.B or ^GSPC",
+                symbol
+            ));
+        }
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    Ok(symbols)
+}
+
+///
+/// Read symbols from a file, an empty file or an invalid symbol is an error.
+///
+fn read_symbols_file(path: &str) -> std::io::Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)?;
+    let symbols = parse_symbols(&content).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    if symbols.is_empty() {
+        Err(Error::new(ErrorKind::InvalidData, "no symbols found"))
+    } else {
+        Ok(symbols)
+    }
+}
+
+///
+/// Merge the symbols passed inline and via file, keeping the first occurrence of each.
+/// Invalid inline symbols fail with `ErrorKind::InvalidInput`.
+///
+fn collect_symbols(inline: Option<&str>, file: Option<&str>) -> std::io::Result<Vec<String>> {
+    let mut symbols: Vec<String> = vec![];
+    let from_file = match file {
+        Some(path) => read_symbols_file(path)?,
+        None => vec![],
+    };
+    let inline = match (inline, file) {
+        (Some(inline), _) => inline,
+        (None, Some(_)) => "",
+        (None, None) => DEFAULT_SYMBOLS,
+    };
+    let inline = parse_symbols(inline).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    for symbol in inline.into_iter().chain(from_file) {
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    Ok(symbols)
+}
+
+///
+/// Parse a date like 2020-07-03, as midnight UTC, or else an RFC3339 timestamp provided for
+/// the CLI option `name`.
+///
+fn parse_date(name: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN).and_utc());
+    }
+    value.parse().map_err(|_| {
+        format!(
+            "Couldn't parse '{}' date '{}', expected a date like 2020-07-03 or RFC3339 like \
+             2020-07-03T12:00:09Z",
+            name, value
+        )
+    })
+}
+
+///
+/// Parse the dates of a period like `parse_date`, ending now without `to`.
+/// The period must not end before it starts.
+///
+fn parse_period(from: &str, to: Option<&str>) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let from = parse_date("from", from)?;
+    let to = match to {
+        Some(to) => parse_date("to", to)?,
+        None => Utc::now(),
+    };
+    if to < from {
+        return Err(format!(
+            "'to' ({}) must not be before 'from' ({})",
+            to.to_rfc3339(),
+            from.to_rfc3339()
+        ));
+    }
+    Ok((from, to))
+}
+
+///
+/// Parse a relative period like 7d, 3w, 6mo or 1y for `--since`, ending at `to` like in
+/// `parse_period`.
+///
+fn parse_since(since: &str, to: Option<&str>) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let to = match to {
+        Some(to) => parse_date("to", to)?,
+        None => Utc::now(),
+    };
+    let error = || {
+        format!(
+            "Couldn't parse 'since' '{}', expected days, weeks, months or years like 7d, 3w, 6mo or 1y",
+            since
+        )
+    };
+    let split = since
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(error)?;
+    let (amount, unit) = since.split_at(split);
+    let amount: u32 = amount.parse().map_err(|_| error())?;
+    let from = match unit {
+        "d" => to.checked_sub_signed(chrono::Duration::days(amount.into())),
+        "w" => to.checked_sub_signed(chrono::Duration::weeks(amount.into())),
+        "mo" => to.checked_sub_months(chrono::Months::new(amount)),
+        "y" => amount
+            .checked_mul(12)
+            .and_then(|months| to.checked_sub_months(chrono::Months::new(months))),
+        _ => None,
+    };
+    Ok((from.ok_or_else(error)?, to))
+}
+
+///
+/// Find the value of `--config` in the raw command line arguments.
+///
+fn config_path(args: &[String]) -> Option<&str> {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.strip_prefix("--config") {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        })
+}
+
+///
+/// Turn the options of a TOML config file into command line arguments, e.g. `sma_window = 10`
+/// into `--sma-window 10`. Lists are joined by commas and `true` sets a flag.
+///
+fn config_args(content: &str) -> Result<Vec<String>, String> {
+    use clap::CommandFactory;
+    let table: std::collections::BTreeMap<String, toml::Value> =
+        toml::from_str(content).map_err(|e| e.to_string())?;
+    let command = Opts::command();
+    let mut args = vec![];
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        if name == "config" || !command.get_arguments().any(|a| a.get_long() == Some(&name)) {
+            return Err(format!("unknown option '{}'", key));
+        }
+        let value = match value {
+            toml::Value::Boolean(false) => continue,
+            toml::Value::Boolean(true) => None,
+            toml::Value::String(s) => Some(s),
+            toml::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|v| v.as_str().map_or_else(|| v.to_string(), String::from))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            other => Some(other.to_string()),
+        };
+        args.push(format!("--{}", name));
+        args.extend(value);
+    }
+    Ok(args)
+}
+
+///
+/// Colors are only used on a terminal unless disabled via `--no-color` or `NO_COLOR`.
+///
+fn use_color(no_color_flag: bool, no_color_env: bool, is_terminal: bool) -> bool {
+    is_terminal && !no_color_flag && !no_color_env
+}
+
+///
+/// Open the output file, appending to or else replacing any existing one.
+///
+fn open_output(path: &str, append: bool) -> std::io::Result<File> {
+    if append {
+        OpenOptions::new().create(true).append(true).open(path)
+    } else {
+        File::create(path)
+    }
+}
+
+///
+/// Join the fields into a row, quoting those that contain the delimiter, a quote or a line
+/// break as in RFC 4180.
+///
+fn csv_row(fields: &[String], delimiter: char) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string())
+}
+
+///
+/// Writes reports in one of the output formats, see `formatter`.
+///
+trait OutputFormatter {
+    /// Write what comes before the reports, e.g. the CSV header.
+    fn write_header(&mut self) -> std::io::Result<()>;
+    /// Write a report as soon as it's done, or keep it for `finish` if the format needs all
+    /// the reports first.
+    fn write_row(&mut self, report: &Report) -> std::io::Result<()>;
+    /// Write the kept reports, if any, and flush the output.
+    fn finish(&mut self) -> std::io::Result<()>;
+}
+
+///
+/// Color a whole row green for a gain or red for a loss, e.g. to point out alerts.
+///
+fn highlight_row(row: &str, change_pct: f64) -> String {
+    if change_pct < 0.0 {
+        row.red().to_string()
+    } else {
+        row.green().to_string()
+    }
+}
+
+/// CSV or TSV rows, written as soon as each report is done.
+struct CsvFormatter<'a, W: Write> {
+    out: W,
+    layout: &'a Layout,
+    /// Whether the rows are colored by their direction
+    highlight: bool,
+}
+
+impl<W: Write> OutputFormatter for CsvFormatter<'_, W> {
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let layout = self.layout;
+        let headers: Vec<String> = layout.columns.iter().map(|c| c.header(layout)).collect();
+        writeln!(self.out, "{}", csv_row(&headers, layout.delimiter))
+    }
+
+    fn write_row(&mut self, report: &Report) -> std::io::Result<()> {
+        let row = report.to_row(self.layout);
+        if self.highlight {
+            writeln!(self.out, "{}", highlight_row(&row, report.change_pct))?;
+        } else {
+            writeln!(self.out, "{}", row)?;
+        }
+        self.out.flush()
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// A JSON array of all reports, or JSON lines written as soon as each report is done.
+struct JsonFormatter<'a, W: Write> {
+    out: W,
+    layout: &'a Layout,
+    lines: bool,
+    /// The reports kept for the array
+    objects: Vec<serde_json::Value>,
+}
+
+impl<W: Write> OutputFormatter for JsonFormatter<'_, W> {
+    fn write_header(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn write_row(&mut self, report: &Report) -> std::io::Result<()> {
+        let object = report.to_json(&self.layout.columns);
+        if self.lines {
+            writeln!(self.out, "{}", object)?;
+            self.out.flush()
+        } else {
+            self.objects.push(object);
+            Ok(())
+        }
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        if !self.lines {
+            let json = if self.layout.pretty {
+                serde_json::to_string_pretty(&self.objects)?
+            } else {
+                serde_json::to_string(&self.objects)?
+            };
+            writeln!(self.out, "{}", json)?;
+            self.objects.clear();
+        }
+        self.out.flush()
+    }
+}
+
+/// An aligned table for reading in a terminal, written once all reports are done since the
+/// widths depend on all of them. Numbers are aligned to the right, so with the same precision
+/// their decimal points line up too.
+struct TableFormatter<'a, W: Write> {
+    out: W,
+    layout: &'a Layout,
+    /// Whether the rows are colored by their direction
+    highlight: bool,
+    /// Whether each table starts with the headers
+    header: bool,
+    /// The cells and the change of the reports kept for the table
+    rows: Vec<(Vec<String>, f64)>,
+}
+
+impl<W: Write> OutputFormatter for TableFormatter<'_, W> {
+    fn write_header(&mut self) -> std::io::Result<()> {
+        self.header = true;
+        Ok(())
+    }
+
+    fn write_row(&mut self, report: &Report) -> std::io::Result<()> {
+        let cells = self
+            .layout
+            .columns
+            .iter()
+            .map(|column| column.csv_cell(report, self.layout.precision))
+            .collect();
+        self.rows.push((cells, report.change_pct));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        let rows = std::mem::take(&mut self.rows);
+        let columns = &self.layout.columns;
+        let headers: Vec<String> = columns.iter().map(|c| c.header(self.layout)).collect();
+        let mut widths = vec![0; columns.len()];
+        let shown_headers = self.header.then_some(&headers);
+        for cells in shown_headers
+            .into_iter()
+            .chain(rows.iter().map(|(cells, _)| cells))
+        {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        // text is aligned to the left, numbers to the right
+        let pad = |column: &Column, cell: &str, width: usize| match column {
+            Column::Timestamp | Column::PeriodStart | Column::Symbol => {
+                format!("{:<width$}", cell)
+            }
+            _ => format!("{:>width$}", cell),
+        };
+        if let Some(headers) = shown_headers {
+            let padded: Vec<String> = columns
+                .iter()
+                .zip(headers)
+                .zip(&widths)
+                .map(|((column, header), width)| pad(column, header, *width))
+                .collect();
+            writeln!(self.out, "{}", padded.join("  ").trim_end())?;
+            let rules: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+            writeln!(self.out, "{}", rules.join("  "))?;
+        }
+        for (cells, change_pct) in rows {
+            let padded: Vec<String> = columns
+                .iter()
+                .zip(&cells)
+                .zip(&widths)
+                .map(|((column, cell), width)| {
+                    let cell = pad(column, cell, *width);
+                    // colored after padding, the escape codes take no room
+                    match column {
+                        Column::Change if self.layout.color_moves && !self.highlight => {
+                            color_move(&cell, change_pct)
+                        }
+                        _ => cell,
+                    }
+                })
+                .collect();
+            let row = padded.join("  ").trim_end().to_string();
+            if self.highlight {
+                writeln!(self.out, "{}", highlight_row(&row, change_pct))?;
+            } else {
+                writeln!(self.out, "{}", row)?;
+            }
+        }
+        self.out.flush()
+    }
+}
+
+///
+/// The formatter of the layout's format, writing to `out`. With `highlight`, rows are colored
+/// by their direction, as for alerts.
+///
+fn formatter<'a, W: Write + 'a>(
+    out: W,
+    layout: &'a Layout,
+    highlight: bool,
+) -> Box<dyn OutputFormatter + 'a> {
+    match layout.format {
+        OutputFormat::Csv | OutputFormat::Tsv => Box::new(CsvFormatter {
+            out,
+            layout,
+            highlight,
+        }),
+        OutputFormat::Json | OutputFormat::Jsonl => Box::new(JsonFormatter {
+            out,
+            layout,
+            lines: layout.format == OutputFormat::Jsonl,
+            objects: vec![],
+        }),
+        OutputFormat::Table => Box::new(TableFormatter {
+            out,
+            layout,
+            highlight,
+            header: false,
+            rows: vec![],
+        }),
+    }
+}
+
+///
+/// A number of periods of the quotes for headers, e.g. 30d for daily bars, 4w for weekly quotes
+/// or 90m for three 30m bars.
+///
+fn periods_label(periods: usize, bar: Bar, interval: Interval) -> String {
+    match (interval, bar) {
+        (Interval::Weekly, _) => format!("{}w", periods),
+        (Interval::Monthly, _) => format!("{}mo", periods),
+        (Interval::Daily, Bar::Minute1) => format!("{}m", periods),
+        (Interval::Daily, Bar::Minute5) => format!("{}m", periods * 5),
+        (Interval::Daily, Bar::Minute15) => format!("{}m", periods * 15),
+        (Interval::Daily, Bar::Minute30) => format!("{}m", periods * 30),
+        (Interval::Daily, Bar::Hour1) => format!("{}h", periods),
+        (Interval::Daily, Bar::Day1) => format!("{}d", periods),
+    }
+}
+
+///
+/// Write the closes of the quotes next to the full SMA, EMA and RSI series in aligned columns.
+/// Every series is lined up with the close it ends on, earlier rows are blank.
+///
+async fn dump_series(
+    out: &mut dyn Write,
+    quotes: &[Quote],
+    settings: &Settings,
+    precision: usize,
+) -> std::io::Result<()> {
+    let closes: Vec<f64> = quotes.iter().map(|q| q.close).collect();
+    let sma = WindowedSMA {
+        window_size: settings.sma_window,
+    };
+    let series = [
+        sma.calculate(&closes).await,
+        ExponentialMovingAverage { period: EMA_PERIOD }
+            .calculate(&closes)
+            .await,
+        Rsi { period: 14 }.calculate(&closes).await,
+    ]
+    .map(|values| {
+        values.map(|mut values| {
+            skip_warmup(&mut values, settings.warmup);
+            values
+        })
+    });
+    let periods = |n| periods_label(n, settings.bar, settings.interval);
+    writeln!(
+        out,
+        "{:<25} {:>12} {:>12} {:>12} {:>12}",
+        "date",
+        "close",
+        format!("{} avg", periods(settings.sma_window)),
+        format!("{} ema", periods(EMA_PERIOD)),
+        format!("{} rsi", periods(14))
+    )?;
+    for (i, (quote, close)) in quotes.iter().zip(&closes).enumerate() {
+        let cells: Vec<String> = series
+            .iter()
+            .map(|values| {
+                let values = values.as_deref().unwrap_or_default();
+                // the last value belongs to the last close
+                (i + values.len())
+                    .checked_sub(closes.len())
+                    .map(|j| format!("{:.p$}", values[j], p = precision))
+                    .unwrap_or_default()
+            })
+            .collect();
+        writeln!(
+            out,
+            "{:<25} {:>12.p$} {:>12} {:>12} {:>12}",
+            quote
+                .timestamp
+                .with_timezone(&settings.timezone)
+                .to_rfc3339(),
+            close,
+            cells[0],
+            cells[1],
+            cells[2],
+            p = precision
+        )?;
+    }
+    Ok(())
+}
+
+///
+/// Write the correlations of the returns of each pair of symbols as a CSV matrix, over the
+/// timestamps both have quotes for. Pairs without a correlation are left blank.
+///
+fn write_correlations(
+    out: &mut dyn Write,
+    symbols: &[String],
+    series: &[Vec<Quote>],
+    precision: usize,
+) -> std::io::Result<()> {
+    let header: Vec<String> = std::iter::once(String::new())
+        .chain(symbols.iter().cloned())
+        .collect();
+    writeln!(out, "{}", csv_row(&header, ','))?;
+    for (symbol, a) in symbols.iter().zip(series) {
+        let row: Vec<String> = std::iter::once(symbol.clone())
+            .chain(series.iter().map(|b| {
+                let (a, b) = aligned_closes(a, b);
+                correlation(&a, &b)
+                    .map(|c| format!("{:.p$}", c, p = precision))
+                    .unwrap_or_default()
+            }))
+            .collect();
+        writeln!(out, "{}", csv_row(&row, ','))?;
+    }
+    Ok(())
+}
+
+///
+/// Whether a report moved by more than the alert threshold in percent.
+///
+fn is_alert(report: &Report, alert_threshold: f64) -> bool {
+    report.change_pct.abs() > alert_threshold
+}
+
+///
+/// Pass a report on to the formatter as soon as it's done. With an alert threshold, only
+/// alerts are.
+///
+fn stream_report(
+    formatter: &mut dyn OutputFormatter,
+    report: &Report,
+    alert_threshold: Option<f64>,
+) -> std::io::Result<()> {
+    if alert_threshold.is_none_or(|threshold| is_alert(report, threshold)) {
+        formatter.write_row(report)?;
+    }
+    Ok(())
+}
+
+///
+/// The reports to show, i.e. all of them or only the alerts with a threshold.
+///
+fn shown_reports(reports: &[Report], alert_threshold: Option<f64>) -> Vec<Report> {
+    reports
+        .iter()
+        .filter(|r| alert_threshold.is_none_or(|threshold| is_alert(r, threshold)))
+        .cloned()
+        .collect()
+}
+
+///
+/// The `n` reports ranking highest by the metric, reports without it rank last.
+///
+fn top_reports(mut reports: Vec<Report>, n: usize, by: SortBy) -> Vec<Report> {
+    let key = |report: &Report| match by {
+        SortBy::Change => report.change_pct,
+        SortBy::Min => report.min,
+        SortBy::Max => report.max,
+        SortBy::Volatility => report.volatility_pct.unwrap_or(f64::NEG_INFINITY),
+    };
+    reports.sort_by(|a, b| key(b).total_cmp(&key(a)));
+    reports.truncate(n);
+    reports
+}
+
+///
+/// Store the reports of a run in the `signals` table, creating it if needed.
+/// Storing a run again replaces its earlier rows instead of duplicating them.
+///
+fn insert_reports(
+    conn: &mut rusqlite::Connection,
+    run_ts: &str,
+    reports: &[Report],
+) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS signals (
+            run_ts TEXT NOT NULL,
+            symbol TEXT NOT NULL,
+            last_price REAL NOT NULL,
+            change_pct REAL NOT NULL,
+            min REAL NOT NULL,
+            max REAL NOT NULL,
+            sma REAL,
+            PRIMARY KEY (run_ts, symbol)
+        )",
+    )?;
+    let tx = conn.transaction()?;
+    {
+        let mut upsert = tx.prepare(
+            "INSERT INTO signals (run_ts, symbol, last_price, change_pct, min, max, sma)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (run_ts, symbol) DO UPDATE SET
+                last_price = excluded.last_price,
+                change_pct = excluded.change_pct,
+                min = excluded.min,
+                max = excluded.max,
+                sma = excluded.sma",
+        )?;
+        for report in reports {
+            upsert.execute(rusqlite::params![
+                run_ts,
+                report.symbol,
+                report.last_price,
+                report.change_pct,
+                report.min,
+                report.max,
+                report.sma.last(),
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+///
+/// Store the reports in the SQLite database at `path` like `insert_reports`, without
+/// blocking the executor.
+///
+async fn save_reports(path: &str, run_ts: &str, reports: &[Report]) -> rusqlite::Result<()> {
+    let (path, run_ts, reports) = (path.to_string(), run_ts.to_string(), reports.to_vec());
+    task::spawn_blocking(move || {
+        let mut conn = rusqlite::Connection::open(path)?;
+        insert_reports(&mut conn, &run_ts, &reports)
+    })
+    .await
+}
+
+/// The reports of all successful symbols and the errors of the failed ones.
+struct Outcome {
+    reports: Vec<Report>,
+    failures: Vec<(String, FetchError)>,
+    succeeded: usize,
+    /// The symbols left out after a failure with `fail_fast`
+    skipped: usize,
+}
+
+impl Outcome {
+    ///
+    /// 0 if all symbols succeeded, 1 if some failed or the rest were skipped after a failure and
+    /// 2 if all failed.
+    ///
+    fn exit_code(&self) -> i32 {
+        if self.failures.is_empty() {
+            0
+        } else if self.succeeded == 0 && self.skipped == 0 {
+            2
+        } else {
+            1
+        }
+    }
+
+    ///
+    /// Add the result of a symbol, handing a report to `on_report` before keeping it. A skipped
+    /// symbol without a report counts as neither succeeded nor failed.
+    ///
+    fn record(
+        &mut self,
+        symbol: &str,
+        result: Result<Option<Report>, FetchError>,
+        on_report: &mut impl FnMut(&mut Report) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        match result {
+            Ok(Some(mut report)) => {
+                on_report(&mut report)?;
+                self.succeeded += 1;
+                self.reports.push(report);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("{}: {}", symbol, e);
+                self.failures.push((symbol.to_string(), e));
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Fetch and process all symbols concurrently, at most `concurrency` at a time, passing each
+/// report to `on_report` as soon as it's done. With `sorted`, all symbols are awaited first
+/// to keep their original order. A failed symbol doesn't affect the others, unless the
+/// settings `fail_fast`, when the symbols not done yet are skipped.
+///
+async fn fetch_reports(
+    provider: &Arc<dyn StockDataProvider>,
+    symbols: &[String],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    settings: &Settings,
+    mut on_report: impl FnMut(&mut Report) -> std::io::Result<()>,
+) -> std::io::Result<Outcome> {
+    let (from, to) = (*from, *to);
+    let jobs: Vec<(usize, String)> = symbols.iter().cloned().enumerate().collect();
+    // tasks are only spawned once there's room for them
+    let mut pending = stream::iter(jobs)
+        .map(|(i, symbol)| {
+            let (provider, settings) = (provider.clone(), settings.clone());
+            task::spawn(async move {
+                let result =
+                    fetch_and_process(provider.as_ref(), &symbol, &from, &to, &settings).await;
+                (i, result)
+            })
+        })
+        .buffer_unordered(settings.concurrency);
+    let progress = if settings.progress && std::io::stderr().is_terminal() {
+        ProgressBar::new(symbols.len() as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    progress.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} symbols {elapsed}")
+            .expect("the template is valid"),
+    );
+
+    let mut outcome = Outcome {
+        reports: vec![],
+        failures: vec![],
+        succeeded: 0,
+        skipped: 0,
+    };
+    let mut done = vec![];
+    let mut received = 0;
+    while let Some((i, result)) = pending.next().await {
+        progress.inc(1);
+        received += 1;
+        let failed = result.is_err();
+        if settings.sorted {
+            done.push((i, result));
+        } else {
+            // rows written to the same terminal mustn't tear the bar
+            progress.suspend(|| outcome.record(&symbols[i], result, &mut on_report))?;
+        }
+        if failed && settings.fail_fast {
+            // dropping the stream leaves the fetches still running to themselves
+            break;
+        }
+    }
+    outcome.skipped = symbols.len() - received;
+    progress.finish_and_clear();
+    done.sort_by_key(|(i, _)| *i);
+    for (i, result) in done {
+        outcome.record(&symbols[i], result, &mut on_report)?;
+    }
+    Ok(outcome)
+}
+
+/// The query of a `GET /signals` request.
+#[derive(Deserialize)]
+struct SignalsQuery {
+    symbols: String,
     from: String,
+    to: Option<String>,
+}
+
+/// The response to a `GET /signals` request.
+#[derive(Serialize)]
+struct SignalsResponse {
+    reports: Vec<Report>,
+    /// The failed symbols with their errors
+    failures: Vec<(String, String)>,
+}
+
+/// What every request of the server shares.
+#[derive(Clone)]
+struct ServerState {
+    provider: Arc<dyn StockDataProvider>,
+    settings: Settings,
+}
+
+///
+/// Handle `GET /signals`, calculating the reports of the symbols just like the CLI does.
+///
+async fn get_signals(req: tide::Request<ServerState>) -> tide::Result {
+    let query: SignalsQuery = req.query()?;
+    let (from, to) = parse_period(&query.from, query.to.as_deref())
+        .map_err(|e| tide::Error::from_str(tide::StatusCode::BadRequest, e))?;
+    let symbols = parse_symbols(&query.symbols)
+        .map_err(|e| tide::Error::from_str(tide::StatusCode::BadRequest, e))?;
+    let state = req.state();
+    let outcome = fetch_reports(
+        &state.provider,
+        &symbols,
+        &from,
+        &to,
+        &state.settings,
+        |_| Ok(()),
+    )
+    .await?;
+    let response = SignalsResponse {
+        reports: outcome.reports,
+        failures: outcome
+            .failures
+            .iter()
+            .map(|(symbol, e)| (symbol.to_string(), e.to_string()))
+            .collect(),
+    };
+    Ok(tide::Body::from_json(&response)?.into())
 }
 
-struct PriceDifference;
-struct MinPrice;
-struct MaxPrice;
-struct WindowedSMA { 
-    window_size: usize 
+///
+/// The routes of the server, /signals and /metrics.
+///
+fn server(provider: Arc<dyn StockDataProvider>, settings: Settings) -> tide::Server<ServerState> {
+    // responses list the reports in the order of the requested symbols
+    let settings = Settings {
+        sorted: true,
+        ..settings
+    };
+    let metrics = settings.metrics.clone();
+    let mut app = tide::with_state(ServerState { provider, settings });
+    app.at("/signals").get(get_signals);
+    app.at("/metrics")
+        .get(move |_| metrics_response(metrics.clone()));
+    app
 }
 
-/// A trait to provide a common interface for all signal calculations.
-#[async_trait]
-trait StockSignal {
-    type SignalType;
-    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType>;
+///
+/// Serve the signals over HTTP until the process is stopped.
+///
+async fn serve(
+    port: u16,
+    provider: Arc<dyn StockDataProvider>,
+    settings: Settings,
+) -> std::io::Result<()> {
+    let app = server(provider, settings);
+    eprintln!("Serving signals on http://0.0.0.0:{}/signals", port);
+    app.listen(("0.0.0.0", port)).await
 }
 
-#[async_trait]
-impl StockSignal for PriceDifference {
-    type SignalType = (f64, f64);
-
-    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
-        if !series.is_empty() {
-            let (first, last) = (series.first().unwrap(), series.last().unwrap());
-            let abs_diff = last - first;
-            let first = if *first == 0.0 { 1.0 } else { *first };
-            let rel_diff = abs_diff / first;
-            Some((abs_diff, rel_diff))
-        } else {
-            None
-        }
-    }
+///
+/// The metrics in the Prometheus text format, as a response to `GET /metrics`.
+///
+async fn metrics_response(metrics: Arc<Metrics>) -> tide::Result {
+    Ok(tide::Response::builder(200)
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics.encode())
+        .build())
 }
 
-#[async_trait]
-impl StockSignal for MaxPrice {
-    type SignalType = f64;
-
-    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
-        if !series.is_empty() {
-            Some(series.iter().fold(f64::MIN, |acc, q| acc.max(*q)))
-        } else {
-            None
-        }
-    }
+///
+/// Serve only the metrics over HTTP, for polling mode.
+///
+async fn serve_metrics(port: u16, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    let mut app = tide::new();
+    app.at("/metrics")
+        .get(move |_| metrics_response(metrics.clone()));
+    eprintln!("Serving metrics on http://0.0.0.0:{}/metrics", port);
+    app.listen(("0.0.0.0", port)).await
 }
 
-#[async_trait]
-impl StockSignal for MinPrice {
-    type SignalType = f64;
-
-    async  fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
-        if series.is_empty() {
-            None
-        } else {
-            Some(series.iter().fold(f64::MAX, |acc, q| acc.min(*q)))
+///
+/// Send on `shutdown` at the first Ctrl-C, so long-running modes can finish what they're doing
+/// and exit cleanly. A second Ctrl-C exits right away.
+///
+fn handle_ctrl_c(shutdown: Sender<()>) -> Result<(), ctrlc::Error> {
+    let requested = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if requested.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
         }
-    }
+        eprintln!("Shutting down, press Ctrl-C again to exit right away");
+        let _ = shutdown.try_send(());
+    })
 }
 
-#[async_trait]
-impl StockSignal for WindowedSMA {
-    type SignalType = Vec<f64>;
-
-    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
-        let n = self.window_size;
-        if !series.is_empty() && n > 1 {
-            Some(
-                series
-                    .windows(n)
-                    .map(|w| w.iter().sum::<f64>() / w.len() as f64)
-                    .collect(),
-            )
-        } else {
-            None
-        }
+///
+/// Write a line per signal of the library: its name with the parameters, what it's calculated
+/// over and what it is.
+///
+fn list_signals(out: &mut dyn Write) -> std::io::Result<()> {
+    let signatures: Vec<String> = SIGNALS
+        .iter()
+        .map(|s| format!("{}({})", s.name, s.parameters.join(", ")))
+        .collect();
+    let width = signatures.iter().map(|s| s.len()).max().unwrap_or(0);
+    for (signal, signature) in SIGNALS.iter().zip(&signatures) {
+        let input = match signal.input {
+            SignalInput::Closes => "closes",
+            SignalInput::Volumes => "volumes",
+            SignalInput::Quotes => "quotes",
+        };
+        writeln!(
+            out,
+            "{:<width$}  {:<7}  {}",
+            signature, input, signal.description
+        )?;
     }
+    Ok(())
 }
 
-
-// async fn process(symbol:&str, from: &DateTime<Utc>, to:&DateTime<Utc>, closes:&Vec<f64>) {
-//     let min = MinPrice {};
-//     let max = MinPrice {};
-//     let diffence = PriceDifference {};
-//     let sma = WindowedSMA { window_size: 30};
-
-//     // min/max of the period. unwrap() because those are Option types
-//     let period_max: f64 = max.calculate(&closes).await?;
-//     let period_min: f64 = min.calculate(&closes).await?;
-//     let (_, pct_change) = diffence.calculate(&closes).await.unwrap_or((0.0, 0.0));
-//     let last_price = *closes.last().await.unwrap_or(&0.0);
-//     let sma = sma.calculate(&closes).await.unwrap_or_default();
-
-//     // a simple way to output CSV data
-//     println!("period start,symbol,price,change %,min,max,30d avg");
-//     println!(
-//         "{},{},${:.2},{:.2}%,${:.2},${:.2},${:.2}",
-//         from.to_rfc3339(),
-//         symbol,
-//         last_price,
-//         pct_change * 100.0,
-//         period_min,
-//         period_max,
-//         sma.last().unwrap_or(&0.0)
-//     );
-// }
-
 ///
-/// Retrieve data from a data source and extract the closing prices. 
-/// Errors during download are mapped onto io::Errors as InvalidData.
+/// Wait for `duration`, returning early with true if a shutdown was requested.
 ///
-async fn fetch_closing_data(
-    symbol: &str,
-    beginning: &DateTime<Utc>,
-    end: &DateTime<Utc>,
-) -> std::io::Result<Vec<f64>> {
-    let provider = yahoo::YahooConnector::new();
-    let response = provider
-        .get_quote_history(symbol, *beginning, *end)
-        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
-    let mut quotes = response
-        .quotes()
-        .map_err(|_| Error::from(ErrorKind::InvalidData))?;
-    if !quotes.is_empty() {
-        quotes.sort_by_cached_key(|k| k.timestamp);
-        Ok(quotes.iter().map(|q| q.adjclose as f64).collect())
-    } else {
-        Ok(vec![])
-    }
+async fn wait_for_shutdown(duration: Duration, shutdown: &Receiver<()>) -> bool {
+    matches!(
+        async_std::future::timeout(duration, shutdown.recv()).await,
+        Ok(Ok(()))
+    )
 }
 
 #[async_std::main]
@@ -168,121 +1881,1574 @@ let asci = r"
     (Stock Data)         __| |__| |      __| |__| |
        by Stephen Mizon  |___||___|      |___||___|
     ";
-    println!("{}", asci.blue());
+    let mut args: Vec<String> = std::env::args().collect();
+    if let Some(path) = config_path(&args).map(String::from) {
+        let config = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| config_args(&content));
+        match config {
+            // the file's options come first, so the ones on the command line override them
+            Ok(config) => drop(args.splice(1..1, config)),
+            Err(e) => {
+                eprintln!("Couldn't read config file '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let opts = Opts::parse_from(args);
+    // diagnostics go to stderr, controlled by RUST_LOG, e.g. RUST_LOG=debug, or only the
+    // warnings unless quiet
+    let default_level = if opts.quiet { "error" } else { "warn" };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_level))
+        .init();
+    if opts.list_signals {
+        return list_signals(&mut std::io::stdout());
+    }
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if opts.quiet || !use_color(opts.no_color, no_color_env, std::io::stdout().is_terminal()) {
+        colored::control::set_override(false);
+    }
+    if !opts.quiet {
+        if opts.output.is_some() {
+            // keep the data file clean, decorations go to stderr then
+            eprintln!("{}", asci.blue());
+            colored::control::set_override(false);
+        } else {
+            println!("{}", asci.blue());
+        }
+    }
+
+    let sma_window = opts.sma_window;
+    if sma_window < 2 {
+        eprintln!("'sma-window' must be at least 2, got {}", sma_window);
+        std::process::exit(1);
+    }
+    if opts.concurrency == 0 {
+        eprintln!("'concurrency' must be at least 1");
+        std::process::exit(1);
+    }
+    if opts.trading_days == 0 {
+        eprintln!("'trading-days' must be at least 1");
+        std::process::exit(1);
+    }
+    if opts.regular_session_only && opts.bar == Bar::Day1 {
+        eprintln!("'regular-session-only' needs intraday bars, see --bar");
+        std::process::exit(1);
+    }
+
+    let mut provider: Arc<dyn StockDataProvider> = match &opts.from_csv {
+        // local files need neither pacing nor caching
+        Some(dir) => Arc::new(CsvFileProvider {
+            dir: PathBuf::from(dir),
+        }),
+        None => Arc::new(YahooProvider {
+            max_retries: opts.max_retries,
+            bar: opts.bar,
+            retry_on: opts.retries_only_on.clone(),
+            price_field: opts.price_field,
+        }),
+    };
+    if opts.timeout_secs > 0 && opts.from_csv.is_none() {
+        provider = Arc::new(TimeoutProvider {
+            inner: provider,
+            timeout: Duration::from_secs(opts.timeout_secs),
+        });
+    }
+    // measured closest to the source, so cache hits and waits for the rate limit don't count
+    let metrics = Arc::new(Metrics::new());
+    provider = Arc::new(MeteredProvider {
+        inner: provider,
+        metrics: metrics.clone(),
+    });
+    if opts.rate_limit_ms > 0 && opts.from_csv.is_none() {
+        provider = Arc::new(RateLimitedProvider {
+            inner: provider,
+            limiter: RateLimiter::new(Duration::from_millis(opts.rate_limit_ms)),
+        });
+    }
+    if !opts.no_cache && opts.from_csv.is_none() {
+        let dir = match &opts.cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => std::env::temp_dir().join("fetch-stock-data-cache"),
+        };
+        provider = Arc::new(CachedProvider {
+            inner: provider,
+            dir,
+            ttl: Duration::from_secs(opts.cache_ttl),
+            bar: opts.bar,
+            price_field: opts.price_field,
+        });
+    }
+    // a symbol keeps its currency, so it's only looked up once per run
+    provider = Arc::new(CurrencyMemo::new(provider));
+    let interval = opts.interval;
+    let sort_by = opts.sort_by.unwrap_or(SortBy::Change);
+    let settings = Settings {
+        sma_window,
+        min_points: opts.min_points.unwrap_or(sma_window.max(EMA_PERIOD)),
+        bar: opts.bar,
+        interval,
+        session: opts.regular_session_only.then(Session::us_equities),
+        jump_threshold: opts.warn_on_jumps,
+        compare_prior: opts.compare_prior,
+        fill: opts.fill,
+        warmup: opts.warmup,
+        progress: opts.progress,
+        with_volume: opts.with_volume,
+        with_volatility: opts.with_volatility || sort_by == SortBy::Volatility,
+        trading_days: opts.trading_days,
+        benchmark: None,
+        concurrency: opts.concurrency,
+        sorted: opts.sorted,
+        fail_fast: opts.fail_fast,
+        metrics,
+        timezone: opts.timezone,
+        currency: opts.currency.as_ref().map(|c| c.to_uppercase()),
+        // only CSV, TSV and tables format prices with their currency, the server returns it
+        with_currency: opts.serve.is_some()
+            || (!matches!(opts.output_format, OutputFormat::Json | OutputFormat::Jsonl)
+                && opts
+                    .columns
+                    .as_ref()
+                    .is_none_or(|columns| columns.iter().any(|c| c.is_price()))),
+    };
+    // the sender stays alive until the end, so the channel only yields on a Ctrl-C
+    let (shutdown_tx, shutdown) = channel::bounded(1);
+    if opts.serve.is_some() || opts.poll.is_some() {
+        if let Err(e) = handle_ctrl_c(shutdown_tx.clone()) {
+            eprintln!("Couldn't install the Ctrl-C handler: {}", e);
+        }
+    }
+    if let Some(port) = opts.serve {
+        let server = Box::pin(serve(port, provider, settings));
+        return match future::select(server, Box::pin(shutdown.recv())).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Ok(()),
+        };
+    }
+    if let Some(port) = opts.metrics_port {
+        let metrics = settings.metrics.clone();
+        task::spawn(async move {
+            if let Err(e) = serve_metrics(port, metrics).await {
+                eprintln!("Couldn't serve metrics on port {}: {}", port, e);
+            }
+        });
+    }
+
+    let period = match &opts.since {
+        Some(since) => parse_since(since, opts.to.as_deref()),
+        None => parse_period(opts.from.as_deref().unwrap_or_default(), opts.to.as_deref()),
+    };
+    let (from, to) = period.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Some(symbol) = &opts.dump {
+        let symbol = symbol.to_uppercase();
+        let quotes = fetch_downsampled(provider.as_ref(), &symbol, &from, &to, &settings)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("{}", failure_message(&symbol, &e));
+                std::process::exit(2);
+            });
+        let mut out = std::io::stdout();
+        dump_series(&mut out, &quotes, &settings, opts.precision.unwrap_or(2)).await?;
+        return out.flush();
+    }
+    if let Some(raw) = &opts.correlate {
+        let symbols = parse_symbols(raw).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        if symbols.len() < 2 {
+            eprintln!("'correlate' needs at least two symbols");
+            std::process::exit(1);
+        }
+        let fetches = symbols
+            .iter()
+            .map(|symbol| fetch_downsampled(provider.as_ref(), symbol, &from, &to, &settings));
+        let results: Vec<_> = stream::iter(fetches)
+            .buffered(settings.concurrency)
+            .collect()
+            .await;
+        let mut series = vec![];
+        for (symbol, result) in symbols.iter().zip(results) {
+            match result {
+                Ok(quotes) => series.push(quotes),
+                Err(e) => {
+                    eprintln!("{}", failure_message(symbol, &e));
+                    std::process::exit(2);
+                }
+            }
+        }
+        let mut out = std::io::stdout();
+        write_correlations(&mut out, &symbols, &series, opts.precision.unwrap_or(2))?;
+        return out.flush();
+    }
+    let symbols = collect_symbols(opts.symbols.as_deref(), opts.symbols_file.as_deref())
+        .unwrap_or_else(|e| {
+            match e.kind() {
+                ErrorKind::InvalidInput => eprintln!("{}", e),
+                _ => eprintln!(
+                    "Couldn't read symbols file '{}': {}",
+                    opts.symbols_file.as_deref().unwrap_or_default(),
+                    e
+                ),
+            }
+            std::process::exit(1);
+        });
+    let weights = opts
+        .weights
+        .clone()
+        .unwrap_or_else(|| vec![1.0; symbols.len()]);
+    if weights.len() != symbols.len() {
+        eprintln!(
+            "'weights' needs a weight per symbol, got {} for {} symbols",
+            weights.len(),
+            symbols.len()
+        );
+        std::process::exit(1);
+    }
+    if weights.iter().sum::<f64>() == 0.0 {
+        eprintln!("'weights' mustn't sum to zero");
+        std::process::exit(1);
+    }
+    // appending to a file with data already in it, which has its header then
+    let continued = opts.append
+        && opts
+            .output
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .is_some_and(|metadata| metadata.len() > 0);
+    let out: Box<dyn Write> = match &opts.output {
+        Some(path) => match open_output(path, opts.append) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Couldn't create output file '{}': {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => Box::new(std::io::stdout()),
+    };
+    let polling = opts.poll.is_some();
+    let format = opts.output_format;
+    let alert_threshold = opts.alert_threshold;
+    let benchmark = opts.benchmark.as_deref();
+    let benchmark_failed = |e: FetchError| {
+        let symbol = benchmark.unwrap_or_default();
+        eprintln!("Benchmark {}", failure_message(symbol, &e))
+    };
+    let with_volume = settings.with_volume;
+    let columns = opts.columns.clone().unwrap_or_else(|| {
+        let mut columns = default_columns(
+            polling,
+            with_volume,
+            benchmark.is_some(),
+            settings.with_volatility,
+        );
+        if settings.compare_prior {
+            // side by side with the change
+            let after_change = columns.iter().position(|c| *c == Column::Change).unwrap() + 1;
+            columns.splice(
+                after_change..after_change,
+                [Column::PriorChange, Column::ChangeVsPrior],
+            );
+        }
+        columns
+    });
+    let layout = Layout {
+        format,
+        precision: opts.precision,
+        delimiter: opts.delimiter.unwrap_or(match format {
+            OutputFormat::Tsv => '\t',
+            _ => ',',
+        }),
+        columns,
+        sma_window,
+        bar: opts.bar,
+        interval: opts.interval,
+        color_moves: opts.color_moves,
+        pretty: opts.pretty,
+    };
+    let mut output = formatter(out, &layout, alert_threshold.is_some());
+    if !continued && !opts.no_header {
+        output.write_header()?;
+    }
+    match opts.poll {
+        Some(seconds) => loop {
+            let to = Utc::now();
+            let timestamp = to.with_timezone(&settings.timezone).to_rfc3339();
+            match with_benchmark(&settings, provider.as_ref(), benchmark, &from, &to).await {
+                Ok(settings) => {
+                    let outcome =
+                        fetch_reports(&provider, &symbols, &from, &to, &settings, |report| {
+                            report.timestamp = Some(timestamp.clone());
+                            stream_report(output.as_mut(), report, alert_threshold)
+                        })
+                        .await?;
+                    output.finish()?;
+                    for (symbol, e) in &outcome.failures {
+                        eprintln!("{}", failure_message(symbol, e));
+                    }
+                    if let Some(path) = &opts.sqlite {
+                        if let Err(e) = save_reports(path, &timestamp, &outcome.reports).await {
+                            eprintln!("Couldn't save the reports to '{}': {}", path, e);
+                        }
+                    }
+                }
+                Err(e) => benchmark_failed(e),
+            }
+            // a Ctrl-C during the cycle has it finish first, the output is flushed by now
+            if wait_for_shutdown(Duration::from_secs(seconds), &shutdown).await {
+                return Ok(());
+            }
+        },
+        None => {
+            let settings = with_benchmark(&settings, provider.as_ref(), benchmark, &from, &to)
+                .await
+                .unwrap_or_else(|e| {
+                    benchmark_failed(e);
+                    std::process::exit(2);
+                });
+            let run_ts = Utc::now().to_rfc3339();
+            let top = opts.top;
+            let outcome = fetch_reports(&provider, &symbols, &from, &to, &settings, |report| {
+                match top {
+                    // ranking needs all the reports first
+                    Some(_) => Ok(()),
+                    None => stream_report(output.as_mut(), report, alert_threshold),
+                }
+            })
+            .await?;
+            if let Some(n) = top {
+                let shown = shown_reports(&outcome.reports, alert_threshold);
+                for report in top_reports(shown, n, sort_by) {
+                    output.write_row(&report)?;
+                }
+            }
+            if opts.portfolio {
+                let portfolio =
+                    portfolio_report(&outcome.reports, &symbols, &weights, &from, &settings);
+                match portfolio.await {
+                    Some(report) => stream_report(output.as_mut(), &report, alert_threshold)?,
+                    None => eprintln!("No portfolio row, the symbols have no day in common"),
+                }
+                if !outcome.failures.is_empty() {
+                    eprintln!("The portfolio leaves out the failed symbols");
+                }
+            }
+            output.finish()?;
+            if !outcome.failures.is_empty() {
+                eprintln!(
+                    "{} of {} symbols failed:",
+                    outcome.failures.len(),
+                    symbols.len()
+                );
+                for (symbol, e) in &outcome.failures {
+                    eprintln!("  {}", failure_message(symbol, e));
+                }
+            }
+            if outcome.skipped > 0 {
+                eprintln!(
+                    "Stopped at the failure, {} symbols were left out",
+                    outcome.skipped
+                );
+            }
+            if let Some(path) = &opts.sqlite {
+                if let Err(e) = save_reports(path, &run_ts, &outcome.reports).await {
+                    eprintln!("Couldn't save the reports to '{}': {}", path, e);
+                    std::process::exit(1);
+                }
+            }
+            let alerted = !shown_reports(&outcome.reports, alert_threshold).is_empty();
+            if alert_threshold.is_some() && alerted && outcome.exit_code() == 0 {
+                std::process::exit(3);
+            }
+            std::process::exit(outcome.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use yahoo_finance_api::YahooError;
+
+    #[test]
+    fn test_Opts() {
+        use clap::CommandFactory;
+        Opts::command().debug_assert();
+
+        let opts = Opts::try_parse_from(["fetch", "--from", "x", "--timezone", "Europe/Zurich"]);
+        assert_eq!(opts.unwrap().timezone, Tz::Europe__Zurich);
+        assert!(
+            Opts::try_parse_from(["fetch", "--from", "x", "--timezone", "Mars/Olympus"]).is_err()
+        );
+
+        let opts = Opts::try_parse_from(["fetch", "--from", "x", "--columns", "symbol,last_price"]);
+        assert_eq!(
+            opts.unwrap().columns,
+            Some(vec![Column::Symbol, Column::LastPrice])
+        );
+        let error = Opts::try_parse_from(["fetch", "--from", "x", "--columns", "symbol,price"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("relative_change"));
+    }
+
+    #[async_std::test]
+    async fn test_process() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let closes = [131.0, 128.5, 126.0, 130.9, 132.1, 128.9];
+        assert_eq!(process("AAPL", &from, &[], 30).await, None);
 
+        let report = process("AAPL", &from, &closes, 30).await.unwrap();
+        assert_eq!(report.symbol, "AAPL");
+        assert_eq!(report.min, 126.0);
+        assert_eq!(report.max, 132.1);
 
+        let all = default_columns(false, false, false, false);
+        let row = report.to_csv(Some(2), &all, ',');
+        let columns: Vec<&str> = row.split(',').collect();
+        assert_eq!(columns[1], "AAPL");
+        assert_eq!(columns[4], "$126.00");
+        assert_eq!(columns[5], "$132.10");
+        assert_ne!(columns[4], columns[5]);
+        // too short for the average, which is no price of 0.0
+        assert_eq!(columns[6], "");
 
-    let opts = Opts::parse();
-    let from:DateTime<Utc> = opts.from.parse().expect("Couldn't parse 'from' date");
-    let to = Utc::now();
+        let report = process("AAPL", &from, &closes, 5).await.unwrap();
+        assert_eq!(report.sma.len(), 2);
 
-    // 
-    let min = MinPrice {};
-    let max = MinPrice {};
-    let diffence = PriceDifference {};
-    let sma = WindowedSMA { window_size: 30};
+        let row = report.to_csv(Some(4), &all, ',');
+        let columns: Vec<&str> = row.split(',').collect();
+        assert_eq!(columns[2], "$128.9000");
+        assert_eq!(columns[3], "-1.6031%");
+        let row = report.to_csv(Some(0), &all, ',');
+        let columns: Vec<&str> = row.split(',').collect();
+        assert_eq!(columns[2], "$129");
+        let row = report.to_csv(
+            Some(2),
+            &[Column::Symbol, Column::Change, Column::Volume],
+            ',',
+        );
+        assert_eq!(row, "AAPL,-1.60%,");
+    }
+
+    #[async_std::test]
+    async fn test_get_signals() {
+        let app = server(Arc::new(StubProvider {}), test_settings());
+        let get = |url: &str| {
+            let request = tide::http::Request::get(format!("http://localhost{}", url).as_str());
+            app.respond(request)
+        };
+
+        let mut response: tide::http::Response =
+            get("/signals?symbols=AAPL,FAIL&from=2021-01-04T00:00:00Z&to=2021-02-04T00:00:00Z")
+                .await
+                .unwrap();
+        assert_eq!(response.status(), tide::StatusCode::Ok);
+        let json: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(json["reports"][0]["symbol"], "AAPL");
+        assert_eq!(json["reports"][0]["last_price"], 3.0);
+        assert_eq!(json["failures"][0][0], "FAIL");
 
-    // a simple way to output a CSV header
-    println!("period start,symbol,price,change %,min,max,30d avg");
-    for symbol in opts.symbols.split(',') {
-        //println!("{}", symbol);
+        let response: tide::http::Response =
+            get("/signals?symbols=AAPL&from=yesterday").await.unwrap();
+        assert_eq!(response.status(), tide::StatusCode::BadRequest);
+        let response: tide::http::Response =
+            get("/signals?from=2021-01-04T00:00:00Z").await.unwrap();
+        assert_eq!(response.status(), tide::StatusCode::BadRequest);
 
-        let closes = fetch_closing_data(&symbol, &from, &to).await?;
+        let mut response: tide::http::Response = get("/metrics").await.unwrap();
+        assert_eq!(response.status(), tide::StatusCode::Ok);
+        let metrics = response.body_string().await.unwrap();
+        assert!(metrics.contains("last_price{symbol=\"AAPL\"} 3"));
+    }
 
+    #[async_std::test]
+    async fn test_wait_for_shutdown() {
+        let (tx, rx) = channel::bounded(1);
+        let start = Instant::now();
+        assert!(!wait_for_shutdown(Duration::from_millis(50), &rx).await);
+        assert!(start.elapsed() >= Duration::from_millis(50));
 
-//        task::block_on(poll_data);
+        tx.try_send(()).unwrap();
+        let start = Instant::now();
+        assert!(wait_for_shutdown(Duration::from_secs(10), &rx).await);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
 
-        if !closes.is_empty() {
-                // min/max of the period. unwrap() because those are Option types
-                let period_max: f64 = max.calculate(&closes).await.unwrap();
-                let period_min: f64 = min.calculate(&closes).await.unwrap();
-                let last_price = *closes.last().unwrap_or(&0.0);
-                let (_, pct_change) = diffence.calculate(&closes).await.unwrap_or((0.0, 0.0));
-                let sma = sma.calculate(&closes).await.unwrap_or_default();
+    #[test]
+    fn test_parse_period() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        assert_eq!(
+            parse_period("2021-01-04T00:00:00Z", Some("2021-01-04T00:00:00Z")),
+            Ok((from, from))
+        );
+        assert!(parse_period("2021-01-04T00:00:00Z", None).unwrap().1 > from);
+        assert!(
+            parse_period("2021-01-04T00:00:00Z", Some("2021-01-03T00:00:00Z"))
+                .unwrap_err()
+                .contains("must not be before")
+        );
+        assert!(parse_period("x", None).unwrap_err().contains("'from'"));
+    }
 
-            // a simple way to output CSV data
-            println!(
-                "{},{},${:.2},{:.2}%,${:.2},${:.2},${:.2}",
-                from.to_rfc3339(),
-                symbol,
-                last_price,
-                pct_change * 100.0,
-                period_min,
-                period_max,
-                sma.last().unwrap_or(&0.0)
+    #[test]
+    fn test_parse_since() {
+        let to = "2021-03-31T12:00:00Z";
+        let since = |since| parse_since(since, Some(to)).map(|(from, _)| from.to_rfc3339());
+        assert_eq!(since("7d"), Ok("2021-03-24T12:00:00+00:00".to_string()));
+        assert_eq!(since("3w"), Ok("2021-03-10T12:00:00+00:00".to_string()));
+        // clamped to the end of the shorter month
+        assert_eq!(since("1mo"), Ok("2021-02-28T12:00:00+00:00".to_string()));
+        assert_eq!(since("1y"), Ok("2020-03-31T12:00:00+00:00".to_string()));
+        assert_eq!(since("0d"), Ok("2021-03-31T12:00:00+00:00".to_string()));
+        for invalid in ["", "d", "7", "7h", "-7d", "1.5y", "99999999999d"] {
+            assert!(
+                since(invalid).unwrap_err().contains("'since'"),
+                "{}",
+                invalid
             );
         }
+        assert!(parse_since("7d", None).is_ok());
+        assert!(parse_since("7d", Some("x")).unwrap_err().contains("'to'"));
+
+        assert!(Opts::try_parse_from(["fetch", "--since", "30d"]).is_ok());
+        assert!(Opts::try_parse_from(["fetch", "--since", "30d", "--from", "x"]).is_err());
+        assert!(Opts::try_parse_from(["fetch"]).is_err());
     }
-    Ok(())
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(non_snake_case)]
-    use super::*;
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("from", "2020-07-03T12:00:09Z"),
+            Ok(Utc.with_ymd_and_hms(2020, 7, 3, 12, 0, 9).unwrap())
+        );
+        assert_eq!(
+            parse_date("from", "2020-07-03"),
+            Ok(Utc.with_ymd_and_hms(2020, 7, 3, 0, 0, 0).unwrap())
+        );
+        let err = parse_date("to", "yesterday").unwrap_err();
+        assert!(err.contains("'to'"));
+        assert!(err.contains("'yesterday'"));
+        assert!(err.contains("RFC3339"));
+        for invalid in ["2020-13-01", "2020-07-03 12:00", "03.07.2020"] {
+            assert!(parse_date("from", invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_split_symbols() {
+        let split = |raw| split_symbols(raw).collect::<Vec<_>>();
+        assert_eq!(split("AAPL MSFT"), vec!["AAPL", "MSFT"]);
+        assert_eq!(
+            split(" aapl,\tMSFT ;; goog\r\n\nbrk.b , "),
+            vec!["AAPL", "MSFT", "GOOG", "BRK.B"]
+        );
+        assert!(split(" ,; \n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        assert_eq!(parse_symbols(""), Ok(vec![]));
+        assert_eq!(parse_symbols(" \n\t, "), Ok(vec![]));
+        assert_eq!(
+            parse_symbols(" aapl , Msft\nAAPL;msft brk.b ^gspc btc-usd cl=f"),
+            Ok(vec!["AAPL", "MSFT", "BRK.B", "^GSPC", "BTC-USD", "CL=F"]
+                .into_iter()
+                .map(String::from)
+                .collect())
+        );
+        for invalid in ["AAPL/MSFT", "AAPL,M$FT", "ÄPFEL", "AVERYLONGTICKERNAME"] {
+            assert!(parse_symbols(invalid)
+                .unwrap_err()
+                .contains("Invalid symbol"));
+        }
+        assert_eq!(parse_symbols(&"A".repeat(MAX_SYMBOL_LEN)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_collect_symbols() {
+        assert_eq!(
+            collect_symbols(None, None).unwrap(),
+            vec!["AAPL", "MSFT", "UBER", "GOOG"]
+        );
+        assert_eq!(
+            collect_symbols(Some(" AAPL,,MSFT ,AAPL"), None).unwrap(),
+            vec!["AAPL", "MSFT"]
+        );
+
+        let path = std::env::temp_dir().join("test_collect_symbols.txt");
+        std::fs::write(&path, "LYFT\n\n  MSFT , FB\nLYFT\n").unwrap();
+        let path = path.to_str().unwrap();
+        assert_eq!(
+            collect_symbols(None, Some(path)).unwrap(),
+            vec!["LYFT", "MSFT", "FB"]
+        );
+        assert_eq!(
+            collect_symbols(Some("AAPL,MSFT"), Some(path)).unwrap(),
+            vec!["AAPL", "MSFT", "LYFT", "FB"]
+        );
+
+        std::fs::write(path, " \n\n").unwrap();
+        assert_eq!(
+            collect_symbols(None, Some(path)).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            collect_symbols(None, Some(path)).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            collect_symbols(Some("AAPL,M$FT"), None).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    ///
+    /// Daily settings with a 2 day SMA and nothing optional, for tests to override.
+    ///
+    fn test_settings() -> Settings {
+        Settings {
+            sma_window: 2,
+            min_points: 0,
+            bar: Bar::Day1,
+            interval: Interval::Daily,
+            session: None,
+            jump_threshold: None,
+            compare_prior: false,
+            fill: false,
+            warmup: 0,
+            progress: false,
+            with_volume: false,
+            with_volatility: false,
+            trading_days: 252,
+            benchmark: None,
+            concurrency: 8,
+            sorted: false,
+            fail_fast: false,
+            metrics: Default::default(),
+            timezone: Tz::UTC,
+            currency: None,
+            with_currency: true,
+        }
+    }
+
+    struct StubProvider;
+
+    #[async_trait]
+    impl StockDataProvider for StubProvider {
+        async fn fetch_quotes(
+            &self,
+            symbol: &str,
+            from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            let quote = |d: i64, close: f64| Quote {
+                timestamp: *from + chrono::Duration::days(d),
+                high: close,
+                low: close,
+                close,
+                volume: 100 * d as u64,
+                adjustment: None,
+            };
+            match symbol {
+                "AAPL" => Ok(vec![quote(0, 1.0), quote(1, 2.0), quote(2, 3.0)]),
+                "SLOW" => {
+                    task::sleep(Duration::from_millis(100)).await;
+                    Ok(vec![quote(0, 1.0)])
+                }
+                "EMPTY" => Ok(vec![]),
+                _ => Err(FetchError::Network(YahooError::ConnectionFailed)),
+            }
+        }
+
+        async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+            Ok((symbol == "SLOW").then(|| "JPY".to_string()))
+        }
+    }
+
+    #[test]
+    fn test_periods_label() {
+        assert_eq!(periods_label(30, Bar::Day1, Interval::Daily), "30d");
+        assert_eq!(periods_label(3, Bar::Minute30, Interval::Daily), "90m");
+        assert_eq!(periods_label(20, Bar::Hour1, Interval::Daily), "20h");
+        assert_eq!(periods_label(30, Bar::Hour1, Interval::Weekly), "30w");
+        assert_eq!(periods_label(12, Bar::Day1, Interval::Monthly), "12mo");
+    }
+
+    #[async_std::test]
+    async fn test_currency() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let mut report = process("7203.T", &from, &[1000.0, 1234.4], 2)
+            .await
+            .unwrap();
+        let columns = [Column::LastPrice, Column::Change];
+        assert_eq!(report.to_csv(None, &columns, ','), "$1234.40,23.44%");
+        report.currency = Some("JPY".to_string());
+        assert_eq!(report.to_csv(None, &columns, ','), "¥1234,23.44%");
+        assert_eq!(report.to_csv(Some(1), &columns, ','), "¥1234.4,23.4%");
+        report.currency = Some("GBP".to_string());
+        assert_eq!(report.to_csv(None, &columns, ','), "£1234.40,23.44%");
+        report.currency = Some("CHF".to_string());
+        assert_eq!(report.to_csv(None, &columns, ','), "CHF 1234.40,23.44%");
+    }
+
+    #[async_std::test]
+    async fn test_fetch_and_process() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 2, 4, 0, 0, 0).unwrap();
+        let provider = StubProvider {};
+
+        let daily = test_settings();
+        let daily_with_volume = Settings {
+            with_volume: true,
+            ..test_settings()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &daily)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.last_price, 3.0);
+        assert_eq!(report.sma, vec![1.5, 2.5]);
+        let too_short = Settings {
+            min_points: 4,
+            ..test_settings()
+        };
+        assert!(fetch_and_process(&provider, "AAPL", &from, &to, &too_short)
+            .await
+            .unwrap()
+            .is_none());
+        let warmed_up = Settings {
+            warmup: 1,
+            ..test_settings()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &warmed_up)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.sma, vec![2.5]);
+        let report = fetch_and_process(
+            &provider,
+            "AAPL",
+            &from,
+            &to,
+            &Settings {
+                warmup: 5,
+                ..test_settings()
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(report.sma.is_empty());
+        assert_eq!(report.last_price, 3.0);
+        assert_eq!(report.volume, None);
+        assert_eq!(report.relative_change_pct, None);
+        assert_eq!(report.currency, None);
+        let in_euros = Settings {
+            currency: Some("EUR".to_string()),
+            ..test_settings()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &in_euros)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.currency.as_deref(), Some("EUR"));
+        let report = fetch_and_process(&provider, "SLOW", &from, &to, &daily)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.currency.as_deref(), Some("JPY"));
+        let without_prices = Settings {
+            with_currency: false,
+            ..test_settings()
+        };
+        let report = fetch_and_process(&provider, "SLOW", &from, &to, &without_prices)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.currency, None);
+        assert_eq!(report.period_start, "2021-01-04T00:00:00+00:00");
+        let new_york = Settings {
+            timezone: "America/New_York".parse().unwrap(),
+            ..test_settings()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &new_york)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.period_start, "2021-01-03T19:00:00-05:00");
+        let with_volatility = Settings {
+            with_volatility: true,
+            trading_days: 4,
+            ..test_settings()
+        };
+        // returns of 100% and 50%
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &with_volatility)
+            .await
+            .unwrap()
+            .unwrap();
+        let expected = 0.125_f64.sqrt() * 2.0 * 100.0;
+        assert!((report.volatility_pct.unwrap() - expected).abs() < 1e-9);
+        let report = fetch_and_process(&provider, "SLOW", &from, &to, &with_volatility)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.volatility_pct, None);
+        assert!(report
+            .to_csv(Some(2), &default_columns(false, false, false, true), ',')
+            .ends_with(",,"));
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &daily_with_volume)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.volume, Some(100.0));
+        let monthly = Settings {
+            interval: Interval::Monthly,
+            ..daily_with_volume.clone()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &monthly)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((report.min, report.max), (3.0, 3.0));
+        assert_eq!(report.volume, Some(300.0));
+        // Jan 4th to Feb 4th with closes on the first three days only
+        let filled = Settings {
+            fill: true,
+            ..daily_with_volume.clone()
+        };
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &filled)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.sma.len(), 31);
+        assert_eq!(report.sma.last(), Some(&3.0));
+        assert_eq!(report.volume, Some(300.0 / 32.0));
+        assert!(matches!(
+            fetch_and_process(&provider, "EMPTY", &from, &to, &daily_with_volume).await,
+            Err(FetchError::NoData)
+        ));
+        assert!(matches!(
+            fetch_and_process(&provider, "FAIL", &from, &to, &daily).await,
+            Err(FetchError::Network(_))
+        ));
+
+        // SLOW only has the first day, where AAPL is at 1.0 as well
+        let with_slow = with_benchmark(&daily, &provider, Some("SLOW"), &from, &to)
+            .await
+            .unwrap();
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &with_slow)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.change_pct, 200.0);
+        assert_eq!(report.relative_change_pct, Some(0.0));
+        // a single day in common has no returns
+        assert_eq!(report.beta, None);
+        let with_aapl = with_benchmark(&daily, &provider, Some("AAPL"), &from, &to)
+            .await
+            .unwrap();
+        assert_eq!(with_aapl.benchmark.as_ref().unwrap().len(), 3);
+        let report = fetch_and_process(&provider, "AAPL", &from, &to, &with_aapl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!((report.beta.unwrap() - 1.0).abs() < 1e-9);
+        assert!(with_benchmark(&daily, &provider, None, &from, &to)
+            .await
+            .unwrap()
+            .benchmark
+            .is_none());
+        assert!(matches!(
+            with_benchmark(&daily, &provider, Some("EMPTY"), &from, &to).await,
+            Err(FetchError::NoData)
+        ));
+    }
+
+    #[test]
+    fn test_failure_message() {
+        assert_eq!(
+            failure_message("APPL", &FetchError::NoData),
+            "no data for APPL (check the ticker and date range)"
+        );
+        assert_eq!(
+            failure_message("AAPL", &FetchError::Network(YahooError::ConnectionFailed)),
+            format!(
+                "AAPL: {}",
+                FetchError::Network(YahooError::ConnectionFailed)
+            )
+        );
+    }
 
     #[test]
-    fn test_PriceDifference_calculate() {
-        let signal = PriceDifference {};
-        assert_eq!(signal.calculate(&[]), None);
-        assert_eq!(signal.calculate(&[1.0]), Some((0.0, 0.0)));
-        assert_eq!(signal.calculate(&[1.0, 0.0]), Some((-1.0, -1.0)));
+    fn test_config_path() {
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(config_path(&args(&["fsd", "--from", "x"])), None);
+        assert_eq!(
+            config_path(&args(&["fsd", "--config", "a.toml"])),
+            Some("a.toml")
+        );
         assert_eq!(
-            signal.calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]),
-            Some((8.0, 4.0))
+            config_path(&args(&["fsd", "--config=b.toml"])),
+            Some("b.toml")
         );
+        assert_eq!(config_path(&args(&["fsd", "--config"])), None);
+    }
+
+    #[test]
+    fn test_config_args() {
+        let config = r#"
+            symbols = ["AAPL", "MSFT"]
+            from = "2021-01-04T00:00:00Z"
+            sma_window = 10
+            quiet = true
+            sorted = false
+        "#;
         assert_eq!(
-            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]),
-            Some((1.0, 1.0))
+            config_args(config).unwrap(),
+            vec![
+                "--from",
+                "2021-01-04T00:00:00Z",
+                "--quiet",
+                "--sma-window",
+                "10",
+                "--symbols",
+                "AAPL,MSFT"
+            ]
         );
+        assert!(config_args("smawindow = 10")
+            .unwrap_err()
+            .contains("'smawindow'"));
+        assert!(config_args("sma_window = ").is_err());
+
+        // the command line overrides the config
+        let mut args = vec!["fsd".to_string()];
+        args.extend(config_args(config).unwrap());
+        args.extend(["--sma-window".to_string(), "20".to_string()]);
+        let opts = Opts::parse_from(args);
+        assert_eq!(opts.sma_window, 20);
+        assert_eq!(opts.symbols.as_deref(), Some("AAPL,MSFT"));
+        assert!(opts.quiet);
     }
 
     #[test]
-    fn test_MinPrice_calculate() {
-        let signal = MinPrice {};
-        assert_eq!(signal.calculate(&[]), None);
-        assert_eq!(signal.calculate(&[1.0]), Some(1.0));
-        assert_eq!(signal.calculate(&[1.0, 0.0]), Some(0.0));
+    fn test_use_color() {
+        assert!(use_color(false, false, true));
+        assert!(!use_color(true, false, true));
+        assert!(!use_color(false, true, true));
+        assert!(!use_color(false, false, false));
+    }
+
+    fn layout(format: OutputFormat, columns: &[Column]) -> Layout {
+        Layout {
+            format,
+            precision: Some(2),
+            delimiter: ',',
+            columns: columns.to_vec(),
+            sma_window: 2,
+            bar: Bar::Day1,
+            interval: Interval::Daily,
+            color_moves: false,
+            pretty: false,
+        }
+    }
+
+    ///
+    /// Write the header and the reports like `main` does.
+    ///
+    fn write_all(out: &mut Vec<u8>, reports: &[Report], layout: &Layout) {
+        let mut formatter = formatter(out, layout, false);
+        formatter.write_header().unwrap();
+        for report in reports {
+            formatter.write_row(report).unwrap();
+        }
+        formatter.finish().unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_write_reports() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let reports = vec![process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap()];
+
+        let mut out = vec![];
+        let columns = default_columns(false, false, false, false);
+        write_all(&mut out, &reports, &layout(OutputFormat::Csv, &columns));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,2d avg,30d ema\n\
+             2021-01-04T00:00:00+00:00,AAPL,$2.00,100.00%,$1.00,$2.00,$1.50,\n"
+        );
+
+        let mut out = vec![];
+        let columns = default_columns(false, false, false, false);
+        write_all(&mut out, &reports, &layout(OutputFormat::Json, &columns));
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[0]["symbol"], "AAPL");
+        assert!(json[0].get("timestamp").is_none());
+        assert!(json[0].get("volume").is_none());
+        assert_eq!(json[0]["sma"], serde_json::json!([1.5]));
+        assert!(json[0]["ema"].is_null());
+
+        let mut out = vec![];
+        let columns = [Column::Volume, Column::Symbol, Column::LastPrice];
+        write_all(&mut out, &reports, &layout(OutputFormat::Csv, &columns));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "volume,symbol,price\n,AAPL,$2.00\n"
+        );
+        let mut out = vec![];
+        write_all(&mut out, &reports, &layout(OutputFormat::Json, &columns));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[{\"volume\":null,\"symbol\":\"AAPL\",\"last_price\":2.0}]\n"
+        );
+        let pretty = Layout {
+            pretty: true,
+            ..layout(OutputFormat::Json, &columns)
+        };
+        let mut out = vec![];
+        write_all(&mut out, &reports, &pretty);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[\n  {\n    \"volume\": null,\n    \"symbol\": \"AAPL\",\n    \"last_price\": 2.0\n  }\n]\n"
+        );
+        // JSON lines stay one per line
+        let mut out = vec![];
+        let lines = Layout {
+            pretty: true,
+            ..layout(OutputFormat::Jsonl, &columns)
+        };
+        write_all(&mut out, &reports, &lines);
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+
+        let mut report = process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap();
+        report.volume = Some(1234.4);
+        let mut out = vec![];
+        let columns = default_columns(false, true, false, false);
+        write_all(&mut out, &[report], &layout(OutputFormat::Csv, &columns));
         assert_eq!(
-            signal.calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]),
-            Some(1.0)
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,2d avg,30d ema,volume\n\
+             2021-01-04T00:00:00+00:00,AAPL,$2.00,100.00%,$1.00,$2.00,$1.50,,1234\n"
         );
+
+        let mut report = process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap();
+        report.relative_change_pct = Some(-12.345);
+        report.beta = Some(1.2345);
+        let mut out = vec![];
+        let columns = default_columns(false, false, true, false);
+        write_all(&mut out, &[report], &layout(OutputFormat::Csv, &columns));
         assert_eq!(
-            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]),
-            Some(0.0)
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,2d avg,30d ema,relative change %,beta\n\
+             2021-01-04T00:00:00+00:00,AAPL,$2.00,100.00%,$1.00,$2.00,$1.50,,-12.35%,1.23\n"
         );
     }
 
     #[test]
-    fn test_MaxPrice_calculate() {
-        let signal = MaxPrice {};
-        assert_eq!(signal.calculate(&[]), None);
-        assert_eq!(signal.calculate(&[1.0]), Some(1.0));
-        assert_eq!(signal.calculate(&[1.0, 0.0]), Some(1.0));
+    fn test_csv_row() {
+        let fields = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(csv_row(&fields(&["a", "b"]), ','), "a,b");
+        assert_eq!(csv_row(&fields(&["a,b", "c"]), ','), "\"a,b\",c");
+        assert_eq!(csv_row(&fields(&["a,b", "c"]), '\t'), "a,b\tc");
+        assert_eq!(
+            csv_row(&fields(&["say \"hi\"", "x\ny"]), ';'),
+            "\"say \"\"hi\"\"\";\"x\ny\""
+        );
+        assert_eq!(csv_row(&[], ','), "");
+    }
+
+    #[async_std::test]
+    async fn test_write_reports_tsv() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let report = process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap();
+        let columns = [Column::Symbol, Column::LastPrice, Column::Change];
+        let tsv = Layout {
+            delimiter: '\t',
+            ..layout(OutputFormat::Tsv, &columns)
+        };
+        let mut out = vec![];
+        write_all(&mut out, &[report], &tsv);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "symbol\tprice\tchange %\nAAPL\t$2.00\t100.00%\n"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_portfolio_report() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let quotes = |closes: &[f64]| -> Vec<Quote> {
+            closes
+                .iter()
+                .enumerate()
+                .map(|(d, close)| Quote {
+                    timestamp: from + chrono::Duration::days(d as i64),
+                    high: *close,
+                    low: *close,
+                    close: *close,
+                    volume: 0,
+                    adjustment: None,
+                })
+                .collect()
+        };
+        let mut up = process("UP", &from, &[1.0, 2.0], 2).await.unwrap();
+        up.quotes = quotes(&[1.0, 2.0]);
+        up.currency = Some("EUR".to_string());
+        let mut down = process("DOWN", &from, &[4.0, 2.0], 2).await.unwrap();
+        down.quotes = quotes(&[4.0, 2.0, 1.0]);
+        down.currency = Some("EUR".to_string());
+        let symbols = ["UP", "DOWN", "UP"].map(String::from);
+        let settings = test_settings();
+        let reports = [up.clone(), down.clone(), up.clone()];
+        // UP is held twice, so with two thirds of the value
+        let portfolio = portfolio_report(&reports, &symbols, &[1.0, 1.0, 1.0], &from, &settings)
+            .await
+            .unwrap();
+        assert_eq!(portfolio.symbol, "PORTFOLIO");
+        assert_eq!(portfolio.last_price, 100.0 * (2.0 * 2.0 + 0.5) / 3.0);
+        assert_eq!(portfolio.min, 100.0);
+        assert_eq!(portfolio.currency.as_deref(), Some("EUR"));
+
+        down.currency = None;
+        let portfolio = portfolio_report(&[up, down], &symbols, &[0.0, 1.0, 0.0], &from, &settings)
+            .await
+            .unwrap();
+        assert_eq!(portfolio.last_price, 50.0);
+        assert_eq!(portfolio.currency, None);
+        assert_eq!(
+            portfolio_report(&[], &symbols, &[1.0; 3], &from, &settings).await,
+            None
+        );
+    }
+
+    #[async_std::test]
+    async fn test_TableFormatter() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let reports = [
+            process("UP", &from, &[1.0, 1.1], 2).await.unwrap(),
+            process("DOWN", &from, &[1.0, 0.8], 2).await.unwrap(),
+        ];
+        let columns = [Column::Symbol, Column::LastPrice, Column::Change];
+        let mut out = vec![];
+        write_all(&mut out, &reports, &layout(OutputFormat::Table, &columns));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "symbol  price  change %\n\
+             ------  -----  --------\n\
+             UP      $1.10    10.00%\n\
+             DOWN    $0.80   -20.00%\n"
+        );
+
+        // each finish writes a table of the reports since the last one
+        let mut out = vec![];
+        let table = layout(OutputFormat::Table, &columns);
+        let mut formatter = formatter(&mut out, &table, false);
+        formatter.write_row(&reports[0]).unwrap();
+        formatter.finish().unwrap();
+        formatter.finish().unwrap();
+        drop(formatter);
+        assert_eq!(String::from_utf8(out).unwrap(), "UP  $1.10  10.00%\n");
+    }
+
+    #[async_std::test]
+    async fn test_write_reports_timestamped() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let mut report = process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap();
+        report.timestamp = Some("2021-02-01T00:00:00+00:00".to_string());
+
+        let mut out = vec![];
+        let columns = default_columns(true, false, false, false);
+        write_all(&mut out, &[report], &layout(OutputFormat::Csv, &columns));
         assert_eq!(
-            signal.calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]),
-            Some(10.0)
+            String::from_utf8(out).unwrap(),
+            "timestamp,period start,symbol,price,change %,min,max,2d avg,30d ema\n\
+             2021-02-01T00:00:00+00:00,2021-01-04T00:00:00+00:00,AAPL,$2.00,100.00%,$1.00,$2.00,$1.50,\n"
         );
+    }
+
+    #[async_std::test]
+    async fn test_fetch_reports() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2021, 2, 4, 0, 0, 0).unwrap();
+        let provider: Arc<dyn StockDataProvider> = Arc::new(StubProvider {});
+        let symbols = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let settings = test_settings();
+        let fetch = |s: Vec<String>, sorted: bool| {
+            let provider = provider.clone();
+            let settings = Settings {
+                sorted,
+                ..settings.clone()
+            };
+            async move {
+                let mut streamed = vec![];
+                let outcome = fetch_reports(&provider, &s, &from, &to, &settings, |r| {
+                    streamed.push(r.symbol.clone());
+                    Ok(())
+                })
+                .await
+                .unwrap();
+                (outcome, streamed)
+            }
+        };
+
+        let (outcome, streamed) = fetch(symbols(&["AAPL", "EMPTY", "AAPL"]), false).await;
+        assert_eq!(outcome.reports.len(), 2);
+        assert_eq!(streamed, vec!["AAPL", "AAPL"]);
+        assert_eq!(outcome.failures[0].0, "EMPTY");
+        assert_eq!(outcome.exit_code(), 1);
+
+        let (outcome, _) = fetch(symbols(&["AAPL"]), false).await;
+        assert_eq!(outcome.exit_code(), 0);
+
+        let (outcome, _) = fetch(symbols(&["FAIL", "AAPL"]), false).await;
+        assert_eq!(outcome.reports.len(), 1);
+        assert_eq!(outcome.failures[0].0, "FAIL");
+        assert_eq!(outcome.exit_code(), 1);
+
+        let (outcome, _) = fetch(symbols(&["FAIL", "FAIL"]), false).await;
+        assert_eq!(outcome.failures.len(), 2);
+        assert_eq!(outcome.exit_code(), 2);
+
+        // reports are passed on as soon as they're done, unless sorted
+        let (outcome, streamed) = fetch(symbols(&["SLOW", "AAPL"]), false).await;
+        assert_eq!(streamed, vec!["AAPL", "SLOW"]);
+        assert_eq!(outcome.reports[0].symbol, "AAPL");
+        let (outcome, streamed) = fetch(symbols(&["SLOW", "AAPL"]), true).await;
+        assert_eq!(streamed, vec!["SLOW", "AAPL"]);
+        assert_eq!(outcome.reports[0].symbol, "SLOW");
+
+        // the first failure skips the symbols not done yet, one at a time for a fixed order
+        let fail_fast = Settings {
+            fail_fast: true,
+            concurrency: 1,
+            ..settings.clone()
+        };
+        let s = symbols(&["AAPL", "FAIL", "SLOW", "SLOW"]);
+        let outcome = fetch_reports(&provider, &s, &from, &to, &fail_fast, |_| Ok(()))
+            .await
+            .unwrap();
+        assert_eq!(outcome.reports.len(), 1);
+        assert_eq!(outcome.failures[0].0, "FAIL");
+        assert_eq!(outcome.skipped, 2);
+        assert_eq!(outcome.exit_code(), 1);
+        let s = symbols(&["FAIL", "SLOW"]);
+        let outcome = fetch_reports(&provider, &s, &from, &to, &fail_fast, |_| Ok(()))
+            .await
+            .unwrap();
+        assert_eq!((outcome.succeeded, outcome.skipped), (0, 1));
+        assert_eq!(outcome.exit_code(), 1);
+
+        // a symbol with too few points is left out without failing, even with fail_fast
+        let min_points = Settings {
+            min_points: 2,
+            ..fail_fast.clone()
+        };
+        let s = symbols(&["SLOW", "AAPL"]);
+        let outcome = fetch_reports(&provider, &s, &from, &to, &min_points, |_| Ok(()))
+            .await
+            .unwrap();
+        assert_eq!(outcome.reports.len(), 1);
+        assert_eq!(outcome.reports[0].symbol, "AAPL");
+        assert!(outcome.failures.is_empty());
+        assert_eq!(outcome.exit_code(), 0);
+    }
+
+    /// Keeps track of how many fetches are running at the same time.
+    struct ConcurrencyProvider {
+        active: AtomicUsize,
+        max_active: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl StockDataProvider for ConcurrencyProvider {
+        async fn fetch_quotes(
+            &self,
+            _symbol: &str,
+            from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_active.fetch_max(active, Ordering::SeqCst);
+            task::sleep(Duration::from_millis(20)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![Quote {
+                timestamp: *from,
+                high: 1.0,
+                low: 1.0,
+                close: 1.0,
+                volume: 0,
+                adjustment: None,
+            }])
+        }
+    }
+
+    #[async_std::test]
+    async fn test_fetch_reports_concurrency() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let counting = Arc::new(ConcurrencyProvider {
+            active: AtomicUsize::new(0),
+            max_active: AtomicUsize::new(0),
+        });
+        let provider: Arc<dyn StockDataProvider> = counting.clone();
+        let symbols: Vec<String> = (0..6).map(|i| format!("S{}", i)).collect();
+        let settings = Settings {
+            concurrency: 2,
+            sorted: true,
+            ..test_settings()
+        };
+        let outcome = fetch_reports(&provider, &symbols, &from, &from, &settings, |_| Ok(()))
+            .await
+            .unwrap();
+        assert_eq!(outcome.reports.len(), 6);
+        assert_eq!(outcome.reports[5].symbol, "S5");
+        assert_eq!(counting.max_active.load(Ordering::SeqCst), 2);
+    }
+
+    #[async_std::test]
+    async fn test_dump_series() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let quotes: Vec<Quote> = (0..16)
+            .map(|d| Quote {
+                timestamp: from + chrono::Duration::days(d),
+                high: 0.0,
+                low: 0.0,
+                close: d as f64 + 1.0,
+                volume: 0,
+                adjustment: None,
+            })
+            .collect();
+        let settings = test_settings();
+        let mut out = vec![];
+        dump_series(&mut out, &quotes, &settings, 2).await.unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        let rows: Vec<Vec<&str>> = dump
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(rows.len(), 17);
         assert_eq!(
-            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]),
-            Some(6.0)
+            rows[0],
+            ["date", "close", "2d", "avg", "30d", "ema", "14d", "rsi"]
         );
+        // too early for any average
+        assert_eq!(rows[1], ["2021-01-04T00:00:00+00:00", "1.00"]);
+        assert_eq!(rows[2], ["2021-01-05T00:00:00+00:00", "2.00", "1.50"]);
+        // only rising prices, and too few of them for the EMA
+        assert_eq!(rows[15][1..], ["15.00", "14.50", "100.00"]);
+        assert_eq!(rows[16][1..], ["16.00", "15.50", "100.00"]);
+        // the columns line up
+        let width = dump.lines().next().unwrap().len();
+        assert!(dump.lines().all(|line| line.len() == width));
+
+        let warmed_up = Settings {
+            warmup: 14,
+            ..settings
+        };
+        let mut out = vec![];
+        dump_series(&mut out, &quotes, &warmed_up, 2).await.unwrap();
+        let dump = String::from_utf8(out).unwrap();
+        let rows: Vec<Vec<&str>> = dump
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        // 15 SMA values leave one, the 2 RSI values none
+        assert_eq!(rows[15][1..], ["15.00"]);
+        assert_eq!(rows[16][1..], ["16.00", "15.50"]);
     }
 
     #[test]
-    fn test_WindowedSMA_calculate() {
-        let series = vec![2.0, 4.5, 5.3, 6.5, 4.7];
+    fn test_write_correlations() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let quotes = |closes: &[f64]| {
+            closes
+                .iter()
+                .enumerate()
+                .map(|(d, close)| Quote {
+                    timestamp: from + chrono::Duration::days(d as i64),
+                    high: *close,
+                    low: *close,
+                    close: *close,
+                    volume: 0,
+                    adjustment: None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let symbols = ["UP", "DOWN", "FLAT"].map(String::from);
+        let series = [
+            quotes(&[100.0, 110.0, 99.0, 108.9]),
+            quotes(&[100.0, 90.0, 99.0, 89.1]),
+            quotes(&[5.0, 5.0, 5.0, 5.0]),
+        ];
+        let mut out = vec![];
+        write_correlations(&mut out, &symbols, &series, 2).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ",UP,DOWN,FLAT\n\
+             UP,1.00,-1.00,\n\
+             DOWN,-1.00,1.00,\n\
+             FLAT,,,\n"
+        );
+    }
+
+    #[async_std::test]
+    async fn test_stream_report() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        colored::control::set_override(false);
+        let up = process("UP", &from, &[1.0, 1.1], 2).await.unwrap();
+        let down = process("DOWN", &from, &[1.0, 0.8], 2).await.unwrap();
+        let stream = |alert_threshold: Option<f64>| {
+            let mut out = vec![];
+            for report in [&up, &down] {
+                let columns = default_columns(false, false, false, false);
+                let layout = layout(OutputFormat::Csv, &columns);
+                let mut formatter = formatter(&mut out, &layout, alert_threshold.is_some());
+                stream_report(formatter.as_mut(), report, alert_threshold).unwrap();
+            }
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(stream(None).lines().count(), 2);
+        let alerts = stream(Some(15.0));
+        assert!(alerts.contains("DOWN"));
+        assert!(!alerts.contains("UP"));
+        assert_eq!(stream(Some(25.0)), "");
 
-        let signal = WindowedSMA { window_size: 3 };
+        let mut out = vec![];
+        let columns = [Column::Symbol, Column::Change];
+        for report in [&up, &down] {
+            let layout = layout(OutputFormat::Jsonl, &columns);
+            stream_report(
+                formatter(&mut out, &layout, true).as_mut(),
+                report,
+                Some(15.0),
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"symbol\":\"DOWN\",\"change_pct\":-19.999999999999996}\n"
+        );
+
+        let reports = [up.clone(), down.clone()];
+        assert_eq!(shown_reports(&reports, None), reports);
+        assert_eq!(shown_reports(&reports, Some(5.0)), reports);
+        assert_eq!(shown_reports(&reports, Some(15.0)), vec![down]);
+
+        // colors are global, so the colored moves are checked here rather than in parallel
+        let crash = process("CRASH", &from, &[1.0, 0.5], 2).await.unwrap();
+        let columns = [Column::Symbol, Column::Change];
+        let colored = Layout {
+            color_moves: true,
+            ..layout(OutputFormat::Csv, &columns)
+        };
+        let mut out = vec![];
+        colored::control::set_override(true);
+        let mut formatter = formatter(&mut out, &colored, false);
+        for report in [&up, &crash] {
+            stream_report(formatter.as_mut(), report, None).unwrap();
+        }
+        drop(formatter);
+        assert_eq!(up.to_row(&layout(OutputFormat::Csv, &columns)), "UP,10.00%");
+        colored::control::set_override(false);
         assert_eq!(
-            signal.calculate(&series),
-            Some(vec![3.9333333333333336, 5.433333333333334, 5.5])
+            String::from_utf8(out).unwrap(),
+            "UP,\u{1b}[1;32m10.00%\u{1b}[0m\nCRASH,\u{1b}[1;31m-50.00%\u{1b}[0m\n"
         );
+        // the values stay plain without colors
+        assert_eq!(crash.to_row(&colored), "CRASH,-50.00%");
+    }
 
-        let signal = WindowedSMA { window_size: 5 };
-        assert_eq!(signal.calculate(&series), Some(vec![4.6]));
+    #[async_std::test]
+    async fn test_top_reports() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let mut flat = process("FLAT", &from, &[2.0, 2.0], 2).await.unwrap();
+        let mut up = process("UP", &from, &[1.0, 1.5], 2).await.unwrap();
+        let down = process("DOWN", &from, &[5.0, 4.0], 2).await.unwrap();
+        flat.volatility_pct = Some(0.0);
+        up.volatility_pct = Some(1.0);
+        let reports = vec![flat, up, down];
+        let symbols = |n, by| {
+            top_reports(reports.clone(), n, by)
+                .iter()
+                .map(|r| r.symbol.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(symbols(2, SortBy::Change), vec!["UP", "FLAT"]);
+        assert_eq!(symbols(1, SortBy::Min), vec!["DOWN"]);
+        assert_eq!(symbols(5, SortBy::Max), vec!["DOWN", "FLAT", "UP"]);
+        assert_eq!(symbols(3, SortBy::Volatility), vec!["UP", "FLAT", "DOWN"]);
+        assert!(symbols(0, SortBy::Change).is_empty());
+
+        let ranked = default_columns(false, false, false, true);
+        let row = reports[1].to_csv(Some(2), &ranked, ',');
+        assert!(row.ends_with(",$1.25,,1.00%"));
+    }
+
+    #[async_std::test]
+    async fn test_insert_reports() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let aapl = process("AAPL", &from, &[1.0, 2.0], 2).await.unwrap();
+        let msft = process("MSFT", &from, &[4.0, 3.0], 30).await.unwrap();
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        let count = |conn: &rusqlite::Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM signals", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        insert_reports(&mut conn, "run 1", &[aapl.clone(), msft.clone()]).unwrap();
+        assert_eq!(count(&conn), 2);
+        let (price, sma): (f64, Option<f64>) = conn
+            .query_row(
+                "SELECT last_price, sma FROM signals WHERE symbol = 'MSFT'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((price, sma), (3.0, None));
+
+        // re-running updates the rows of the run, a new run adds rows
+        let updated = Report {
+            last_price: 2.5,
+            ..aapl.clone()
+        };
+        insert_reports(&mut conn, "run 1", &[updated]).unwrap();
+        assert_eq!(count(&conn), 2);
+        let (price, sma): (f64, Option<f64>) = conn
+            .query_row(
+                "SELECT last_price, sma FROM signals WHERE symbol = 'AAPL'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((price, sma), (2.5, Some(1.5)));
+        insert_reports(&mut conn, "run 2", &[aapl]).unwrap();
+        assert_eq!(count(&conn), 3);
+    }
 
-        let signal = WindowedSMA { window_size: 10 };
-        assert_eq!(signal.calculate(&series), Some(vec![]));
+    #[async_std::test]
+    async fn test_Report_json() {
+        let from = Utc.with_ymd_and_hms(2021, 1, 4, 0, 0, 0).unwrap();
+        let report = process("AAPL", &from, &[1.0, 2.0], 30).await.unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["period_start"], "2021-01-04T00:00:00+00:00");
+        assert_eq!(json["symbol"], "AAPL");
+        assert_eq!(json["last_price"], 2.0);
+        assert_eq!(json["change_pct"], 100.0);
+        assert_eq!(json["min"], 1.0);
+        assert_eq!(json["max"], 2.0);
+        assert_eq!(json["sma"], serde_json::Value::Null);
+        assert_eq!(json["ema"], serde_json::Value::Null);
+        let report = process("AAPL", &from, &[1.0, 2.0, 4.0], 2).await.unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["sma"], 3.0);
     }
 }
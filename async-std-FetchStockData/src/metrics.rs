@@ -0,0 +1,152 @@
+//!
+//! Operational metrics in the Prometheus text format, for monitoring polling and server mode.
+//!
+use crate::data::{FetchError, Quote, StockDataProvider};
+use async_trait::async_trait;
+use chrono::prelude::*;
+use prometheus::{Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, Opts, Registry};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// The counters, gauges and histograms of a run, registered with their own registry.
+pub struct Metrics {
+    registry: Registry,
+    /// Requests for the quotes of a symbol.
+    pub fetch_requests: IntCounter,
+    /// Requests that failed, for whichever reason.
+    pub fetch_errors: IntCounter,
+    /// The seconds a request took, successful or not.
+    pub fetch_latency: Histogram,
+    /// The latest price per symbol.
+    pub last_price: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let fetch_requests = IntCounter::new(
+            "fetch_requests_total",
+            "Requests for the quotes of a symbol",
+        )
+        .unwrap();
+        let fetch_errors =
+            IntCounter::new("fetch_errors_total", "Requests for quotes that failed").unwrap();
+        let fetch_latency = Histogram::with_opts(HistogramOpts::new(
+            "fetch_latency_seconds",
+            "The time requests for quotes took",
+        ))
+        .unwrap();
+        let last_price = GaugeVec::new(
+            Opts::new("last_price", "The latest price of a symbol"),
+            &["symbol"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        // the names are distinct, so registering can't fail
+        registry.register(Box::new(fetch_requests.clone())).unwrap();
+        registry.register(Box::new(fetch_errors.clone())).unwrap();
+        registry.register(Box::new(fetch_latency.clone())).unwrap();
+        registry.register(Box::new(last_price.clone())).unwrap();
+        Metrics {
+            registry,
+            fetch_requests,
+            fetch_errors,
+            fetch_latency,
+            last_price,
+        }
+    }
+
+    ///
+    /// All metrics in the Prometheus text exposition format.
+    ///
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        prometheus::TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps another provider and counts and times its requests.
+pub struct MeteredProvider {
+    pub inner: Arc<dyn StockDataProvider>,
+    pub metrics: Arc<Metrics>,
+}
+
+#[async_trait]
+impl StockDataProvider for MeteredProvider {
+    async fn fetch_quotes(
+        &self,
+        symbol: &str,
+        from: &DateTime<Utc>,
+        to: &DateTime<Utc>,
+    ) -> Result<Vec<Quote>, FetchError> {
+        self.metrics.fetch_requests.inc();
+        let start = Instant::now();
+        let result = self.inner.fetch_quotes(symbol, from, to).await;
+        self.metrics
+            .fetch_latency
+            .observe(start.elapsed().as_secs_f64());
+        if result.is_err() {
+            self.metrics.fetch_errors.inc();
+        }
+        result
+    }
+
+    async fn fetch_currency(&self, symbol: &str) -> Result<Option<String>, FetchError> {
+        // the metrics are about the quotes
+        self.inner.fetch_currency(symbol).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    struct StubProvider;
+
+    #[async_trait]
+    impl StockDataProvider for StubProvider {
+        async fn fetch_quotes(
+            &self,
+            symbol: &str,
+            _from: &DateTime<Utc>,
+            _to: &DateTime<Utc>,
+        ) -> Result<Vec<Quote>, FetchError> {
+            match symbol {
+                "AAPL" => Ok(vec![]),
+                _ => Err(FetchError::NoData),
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn test_MeteredProvider_fetch_quotes() {
+        let metrics = Arc::new(Metrics::new());
+        let provider = MeteredProvider {
+            inner: Arc::new(StubProvider),
+            metrics: metrics.clone(),
+        };
+        let now = Utc::now();
+        assert!(provider.fetch_quotes("AAPL", &now, &now).await.is_ok());
+        assert!(provider.fetch_quotes("MSFT", &now, &now).await.is_err());
+        metrics.last_price.with_label_values(&["AAPL"]).set(1.5);
+
+        assert_eq!(metrics.fetch_requests.get(), 2);
+        assert_eq!(metrics.fetch_errors.get(), 1);
+        assert_eq!(metrics.fetch_latency.get_sample_count(), 2);
+        let text = metrics.encode();
+        assert!(text.contains("fetch_requests_total 2"));
+        assert!(text.contains("fetch_errors_total 1"));
+        assert!(text.contains("fetch_latency_seconds_count 2"));
+        assert!(text.contains("last_price{symbol=\"AAPL\"} 1.5"));
+    }
+}
@@ -0,0 +1,1738 @@
+//!
+//! Signals calculated over a series of closing prices or quotes.
+//!
+use crate::data::Quote;
+use async_trait::async_trait;
+
+/// A trait to provide a common interface for all signal calculations.
+#[async_trait]
+pub trait StockSignal {
+    type SignalType;
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType>;
+}
+
+/// Like `StockSignal`, for signals that need the full quotes instead of closing prices.
+#[async_trait]
+pub trait QuoteSignal {
+    type SignalType;
+    async fn calculate(&self, quotes: &[Quote]) -> Option<Self::SignalType>;
+}
+
+/// The absolute and relative change between the first and the last price of a series.
+pub struct PriceDifference;
+
+#[async_trait]
+impl StockSignal for PriceDifference {
+    type SignalType = (f64, f64);
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if !series.is_empty() {
+            let (first, last) = (series.first().unwrap(), series.last().unwrap());
+            let abs_diff = last - first;
+            let first = if *first == 0.0 { 1.0 } else { *first };
+            let rel_diff = abs_diff / first;
+            Some((abs_diff, rel_diff))
+        } else {
+            None
+        }
+    }
+}
+
+/// The lowest price of a series.
+pub struct MinPrice;
+
+#[async_trait]
+impl StockSignal for MinPrice {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.is_empty() {
+            None
+        } else {
+            Some(series.iter().fold(f64::MAX, |acc, q| acc.min(*q)))
+        }
+    }
+}
+
+/// The highest price of a series.
+pub struct MaxPrice;
+
+#[async_trait]
+impl StockSignal for MaxPrice {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if !series.is_empty() {
+            Some(series.iter().fold(f64::MIN, |acc, q| acc.max(*q)))
+        } else {
+            None
+        }
+    }
+}
+
+/// The simple moving average over each window of `window_size` prices.
+pub struct WindowedSMA {
+    pub window_size: usize,
+}
+
+#[async_trait]
+impl StockSignal for WindowedSMA {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.window_size;
+        if !series.is_empty() && n > 1 {
+            Some(
+                series
+                    .windows(n)
+                    .map(|w| w.iter().sum::<f64>() / w.len() as f64)
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// The exponential moving average with a smoothing factor of `2 / (period + 1)`,
+/// seeded with the simple average of the first `period` prices.
+pub struct ExponentialMovingAverage {
+    pub period: usize,
+}
+
+#[async_trait]
+impl StockSignal for ExponentialMovingAverage {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if !series.is_empty() && n >= 1 {
+            if series.len() < n {
+                return Some(vec![]);
+            }
+            // seed with the SMA of the first period, then smooth with 2 / (n + 1)
+            let alpha = 2.0 / (n as f64 + 1.0);
+            let seed = series[..n].iter().sum::<f64>() / n as f64;
+            let mut ema = Vec::with_capacity(series.len() - n + 1);
+            ema.push(seed);
+            for price in &series[n..] {
+                let prev = *ema.last().unwrap();
+                ema.push(alpha * price + (1.0 - alpha) * prev);
+            }
+            Some(ema)
+        } else {
+            None
+        }
+    }
+}
+
+/// The relative strength index over `period` price changes, using Wilder's smoothing.
+pub struct Rsi {
+    pub period: usize,
+}
+
+#[async_trait]
+impl StockSignal for Rsi {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if n >= 1 && series.len() > n {
+            let changes: Vec<f64> = series.windows(2).map(|w| w[1] - w[0]).collect();
+            let rsi = |gain: f64, loss: f64| {
+                if loss == 0.0 {
+                    100.0
+                } else {
+                    100.0 - 100.0 / (1.0 + gain / loss)
+                }
+            };
+
+            // the first averages are plain means, after that Wilder's smoothing applies
+            let mut avg_gain = changes[..n].iter().map(|c| c.max(0.0)).sum::<f64>() / n as f64;
+            let mut avg_loss = changes[..n].iter().map(|c| (-c).max(0.0)).sum::<f64>() / n as f64;
+            let mut result = vec![rsi(avg_gain, avg_loss)];
+            for c in &changes[n..] {
+                avg_gain = (avg_gain * (n - 1) as f64 + c.max(0.0)) / n as f64;
+                avg_loss = (avg_loss * (n - 1) as f64 + (-c).max(0.0)) / n as f64;
+                result.push(rsi(avg_gain, avg_loss));
+            }
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+/// The largest peak-to-trough decline as a negative fraction, e.g. -0.23 for 23%.
+pub struct MaxDrawdown;
+
+#[async_trait]
+impl StockSignal for MaxDrawdown {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.is_empty() {
+            None
+        } else {
+            let (_, drawdown) = series.iter().fold((f64::MIN, 0.0), |(peak, drawdown), q| {
+                let peak = peak.max(*q);
+                let peak_divisor = if peak == 0.0 { 1.0 } else { peak };
+                (peak, f64::min(drawdown, (q - peak) / peak_divisor))
+            });
+            Some(drawdown)
+        }
+    }
+}
+
+///
+/// Simple returns between consecutive prices, a previous price of 0.0 is treated as 1.0.
+///
+pub fn simple_returns(series: &[f64]) -> Vec<f64> {
+    series
+        .windows(2)
+        .map(|w| {
+            let prev = if w[0] == 0.0 { 1.0 } else { w[0] };
+            (w[1] - w[0]) / prev
+        })
+        .collect()
+}
+
+/// The simple return from each price to the next, `price[t] / price[t - 1] - 1`, as a signal
+/// of its own. A previous price of 0.0 is treated like in `simple_returns`.
+pub struct DailyReturns;
+
+#[async_trait]
+impl StockSignal for DailyReturns {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 {
+            None
+        } else {
+            Some(simple_returns(series))
+        }
+    }
+}
+
+///
+/// Mean and sample standard deviation of the values.
+/// A single value has no spread, so its deviation is 0.0 rather than a division by zero.
+///
+pub(crate) fn mean_and_std_dev(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let squares = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+    (mean, (squares / (n - 1.0).max(1.0)).sqrt())
+}
+
+/// Sample standard deviation of daily returns, annualized with `trading_days` if provided.
+pub struct Volatility {
+    pub trading_days: Option<usize>,
+}
+
+#[async_trait]
+impl StockSignal for Volatility {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 {
+            None
+        } else {
+            let (_, std_dev) = mean_and_std_dev(&simple_returns(series));
+            match self.trading_days {
+                Some(days) => Some(std_dev * (days as f64).sqrt()),
+                None => Some(std_dev),
+            }
+        }
+    }
+}
+
+/// The mean daily return in excess of the daily `risk_free_rate`, divided by the standard
+/// deviation of the daily returns. The ratio is daily unless `trading_days` is provided
+/// to annualize it with `sqrt(trading_days)`, e.g. 252.
+pub struct SharpeRatio {
+    pub risk_free_rate: f64,
+    pub trading_days: Option<usize>,
+}
+
+#[async_trait]
+impl StockSignal for SharpeRatio {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 {
+            return None;
+        }
+        let (mean, std_dev) = mean_and_std_dev(&simple_returns(series));
+        if std_dev == 0.0 {
+            None
+        } else {
+            let sharpe = (mean - self.risk_free_rate) / std_dev;
+            match self.trading_days {
+                Some(days) => Some(sharpe * (days as f64).sqrt()),
+                None => Some(sharpe),
+            }
+        }
+    }
+}
+
+/// The (lower, middle, upper) bands for each window of `period` prices. The middle band
+/// is the simple moving average, the others are `num_std` sample standard deviations away.
+pub struct BollingerBands {
+    pub period: usize,
+    pub num_std: f64,
+}
+
+#[async_trait]
+impl StockSignal for BollingerBands {
+    type SignalType = Vec<(f64, f64, f64)>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if n >= 2 && series.len() >= n {
+            Some(
+                series
+                    .windows(n)
+                    .map(|w| {
+                        let (mean, std_dev) = mean_and_std_dev(w);
+                        let width = self.num_std * std_dev;
+                        (mean - width, mean, mean + width)
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// The moving average convergence/divergence as (macd line, signal line, histogram), aligned
+/// with the signal line. The macd line is the `fast` minus the `slow` EMA, the signal line is
+/// the `signal` EMA of the macd line and the histogram is their difference.
+pub struct Macd {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Default for Macd {
+    /// The classic 12/26/9 configuration.
+    fn default() -> Self {
+        Macd {
+            fast: 12,
+            slow: 26,
+            signal: 9,
+        }
+    }
+}
+
+#[async_trait]
+impl StockSignal for Macd {
+    type SignalType = Vec<(f64, f64, f64)>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if self.fast < 1 || self.signal < 1 || self.slow <= self.fast || series.len() < self.slow {
+            return None;
+        }
+        let fast = ExponentialMovingAverage { period: self.fast }
+            .calculate(series)
+            .await?;
+        let slow = ExponentialMovingAverage { period: self.slow }
+            .calculate(series)
+            .await?;
+        // the fast EMA starts earlier, so skip ahead to where the slow one begins
+        let offset = self.slow - self.fast;
+        let macd: Vec<f64> = slow
+            .iter()
+            .zip(&fast[offset..])
+            .map(|(slow, fast)| fast - slow)
+            .collect();
+        if macd.len() < self.signal {
+            // not enough of the macd line for a single signal value yet
+            return Some(vec![]);
+        }
+        let signal = ExponentialMovingAverage {
+            period: self.signal,
+        }
+        .calculate(&macd)
+        .await?;
+        Some(
+            macd[self.signal - 1..]
+                .iter()
+                .zip(signal)
+                .map(|(macd, signal)| (*macd, signal, macd - signal))
+                .collect(),
+        )
+    }
+}
+
+/// The average of a volume series, i.e. the shares traded per period.
+pub struct AverageVolume;
+
+#[async_trait]
+impl StockSignal for AverageVolume {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.is_empty() {
+            None
+        } else {
+            Some(series.iter().sum::<f64>() / series.len() as f64)
+        }
+    }
+}
+
+/// The momentum over `period` prices, i.e. the relative change to the price `period` steps earlier.
+pub struct RateOfChange {
+    pub period: usize,
+}
+
+#[async_trait]
+impl StockSignal for RateOfChange {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if n == 0 || series.len() < n + 1 {
+            return None;
+        }
+        Some(
+            series
+                .iter()
+                .zip(&series[n..])
+                .map(|(lagged, price)| {
+                    let divisor = if *lagged == 0.0 { 1.0 } else { *lagged };
+                    (price - lagged) / divisor
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The average true range over `period` quotes, using Wilder's smoothing.
+pub struct Atr {
+    pub period: usize,
+}
+
+#[async_trait]
+impl QuoteSignal for Atr {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, quotes: &[Quote]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if n == 0 || quotes.len() <= n {
+            return None;
+        }
+        // the true range also covers a gap from the previous close
+        let true_ranges: Vec<f64> = quotes
+            .windows(2)
+            .map(|w| {
+                let (prev_close, q) = (w[0].close, &w[1]);
+                (q.high - q.low)
+                    .max((q.high - prev_close).abs())
+                    .max((q.low - prev_close).abs())
+            })
+            .collect();
+
+        let mut atr = true_ranges[..n].iter().sum::<f64>() / n as f64;
+        let mut result = vec![atr];
+        for tr in &true_ranges[n..] {
+            atr = (atr * (n - 1) as f64 + tr) / n as f64;
+            result.push(atr);
+        }
+        Some(result)
+    }
+}
+
+/// The direction in which the short SMA crosses the long one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrossDirection {
+    /// The short SMA rises above the long SMA.
+    Golden,
+    /// The short SMA falls below the long SMA.
+    Death,
+}
+
+/// The crossovers between a `short` and a `long` SMA, indexed into the price series.
+pub struct SmaCross {
+    pub short: usize,
+    pub long: usize,
+}
+
+#[async_trait]
+impl StockSignal for SmaCross {
+    type SignalType = Vec<(usize, CrossDirection)>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if self.short < 2 || self.short >= self.long || series.len() < self.long {
+            return None;
+        }
+        let short = WindowedSMA {
+            window_size: self.short,
+        };
+        let long = WindowedSMA {
+            window_size: self.long,
+        };
+        let (short, long) = (
+            short.calculate(series).await?,
+            long.calculate(series).await?,
+        );
+        // both end at the last price, the short one just starts earlier
+        let short = &short[self.long - self.short..];
+
+        let mut crosses = vec![];
+        let mut side = 0.0;
+        for (i, (s, l)) in short.iter().zip(&long).enumerate() {
+            let diff = s - l;
+            if diff == 0.0 {
+                continue;
+            }
+            if side < 0.0 && diff > 0.0 {
+                crosses.push((i + self.long - 1, CrossDirection::Golden));
+            } else if side > 0.0 && diff < 0.0 {
+                crosses.push((i + self.long - 1, CrossDirection::Death));
+            }
+            side = diff;
+        }
+        Some(crosses)
+    }
+}
+
+/// How many standard deviations each price is away from the mean of its window of `period` prices.
+pub struct ZScore {
+    pub period: usize,
+}
+
+#[async_trait]
+impl StockSignal for ZScore {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.period;
+        if n < 2 || series.len() < n {
+            return None;
+        }
+        series
+            .windows(n)
+            .map(|w| {
+                let (mean, std_dev) = mean_and_std_dev(w);
+                // a flat window has no meaningful score
+                if std_dev == 0.0 {
+                    None
+                } else {
+                    Some((w[n - 1] - mean) / std_dev)
+                }
+            })
+            .collect()
+    }
+}
+
+/// The median price of a series, averaging the two middle prices for an even length.
+pub struct MedianPrice;
+
+#[async_trait]
+impl StockSignal for MedianPrice {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.is_empty() {
+            return None;
+        }
+        // sort a copy, the caller's series stays as it is
+        let mut sorted = series.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        if sorted.len().is_multiple_of(2) {
+            Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+        } else {
+            Some(sorted[mid])
+        }
+    }
+}
+
+/// The total return from the first price up to each price of a series.
+pub struct CumulativeReturn;
+
+#[async_trait]
+impl StockSignal for CumulativeReturn {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let first = *series.first()?;
+        let divisor = if first == 0.0 { 1.0 } else { first };
+        Some(series.iter().map(|p| (p - first) / divisor).collect())
+    }
+}
+
+/// The ordinary least-squares fit of the prices against their index, as the slope per period
+/// (per day for daily data) and R², how much of the series' variance the trend explains.
+pub struct LinearTrend;
+
+#[async_trait]
+impl StockSignal for LinearTrend {
+    type SignalType = (f64, f64);
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 {
+            return None;
+        }
+        let n = series.len() as f64;
+        let mean_x = (n - 1.0) / 2.0;
+        let mean_y = series.iter().sum::<f64>() / n;
+        let (mut sxy, mut sxx, mut syy) = (0.0, 0.0, 0.0);
+        for (i, y) in series.iter().enumerate() {
+            let (dx, dy) = (i as f64 - mean_x, y - mean_y);
+            sxy += dx * dy;
+            sxx += dx * dx;
+            syy += dy * dy;
+        }
+        let slope = sxy / sxx;
+        // a flat series is fitted perfectly by a flat line
+        let r_squared = if syy == 0.0 {
+            1.0
+        } else {
+            sxy * sxy / (sxx * syy)
+        };
+        Some((slope, r_squared))
+    }
+}
+
+/// The stochastic oscillator: %K places the close within the range of the last `k_period`
+/// quotes, from 0 at the lowest low to 100 at the highest high, and %D is the `d_period` simple
+/// average of %K. The (%K, %D) pairs start at the first quote with both values.
+pub struct StochasticOscillator {
+    pub k_period: usize,
+    pub d_period: usize,
+}
+
+#[async_trait]
+impl QuoteSignal for StochasticOscillator {
+    type SignalType = Vec<(f64, f64)>;
+
+    async fn calculate(&self, quotes: &[Quote]) -> Option<Self::SignalType> {
+        let (k, d) = (self.k_period, self.d_period);
+        if k == 0 || d == 0 || quotes.len() < k + d - 1 {
+            return None;
+        }
+        let percent_k: Vec<f64> = quotes
+            .windows(k)
+            .map(|w| {
+                let close = w[k - 1].close;
+                let lowest = w.iter().map(|q| q.low).fold(f64::INFINITY, f64::min);
+                let highest = w.iter().map(|q| q.high).fold(f64::NEG_INFINITY, f64::max);
+                // without any range the close is neither high nor low
+                if highest == lowest {
+                    50.0
+                } else {
+                    100.0 * (close - lowest) / (highest - lowest)
+                }
+            })
+            .collect();
+        Some(
+            percent_k
+                .windows(d)
+                .map(|w| (w[d - 1], w.iter().sum::<f64>() / d as f64))
+                .collect(),
+        )
+    }
+}
+
+/// The on-balance volume: the running total of the volume, added on days the close rose and
+/// subtracted on days it fell, starting at 0 with the first quote.
+pub struct OnBalanceVolume;
+
+#[async_trait]
+impl QuoteSignal for OnBalanceVolume {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, quotes: &[Quote]) -> Option<Self::SignalType> {
+        if quotes.len() < 2 {
+            return None;
+        }
+        let mut obv = 0.0;
+        let mut result = vec![obv];
+        for w in quotes.windows(2) {
+            let (prev, q) = (&w[0], &w[1]);
+            if q.close > prev.close {
+                obv += q.volume as f64;
+            } else if q.close < prev.close {
+                obv -= q.volume as f64;
+            }
+            result.push(obv);
+        }
+        Some(result)
+    }
+}
+
+/// The weighted moving average over windows of `window_size` prices, weighting the prices of a
+/// window linearly from 1 for the oldest to `window_size` for the most recent.
+pub struct WeightedMovingAverage {
+    pub window_size: usize,
+}
+
+#[async_trait]
+impl StockSignal for WeightedMovingAverage {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.window_size;
+        if series.is_empty() || n < 2 {
+            return None;
+        }
+        let total_weight = (n * (n + 1) / 2) as f64;
+        Some(
+            series
+                .windows(n)
+                .map(|w| {
+                    w.iter()
+                        .enumerate()
+                        .map(|(i, p)| p * (i + 1) as f64)
+                        .sum::<f64>()
+                        / total_weight
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The percentile rank (0-100) of the last price within the series, i.e. the share of
+/// prices below it.
+pub struct PercentileRank;
+
+#[async_trait]
+impl StockSignal for PercentileRank {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let last = *series.last()?;
+        let below = series.iter().filter(|p| **p < last).count();
+        Some(100.0 * below as f64 / series.len() as f64)
+    }
+}
+
+/// The absolute change of a series like `PriceDifference` and its relative change annualized
+/// as `(1 + rel_diff)^(trading_days / periods) - 1`, with a period between each two prices,
+/// so changes over different date ranges become comparable. `trading_days` is the number of
+/// periods per year, e.g. 252 for daily prices.
+pub struct AnnualizedReturn {
+    pub trading_days: usize,
+}
+
+#[async_trait]
+impl StockSignal for AnnualizedReturn {
+    type SignalType = (f64, f64);
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 || self.trading_days == 0 {
+            return None;
+        }
+        let (abs_diff, rel_diff) = PriceDifference {}.calculate(series).await?;
+        // losing more than everything, e.g. with negative prices, can't be compounded
+        if rel_diff < -1.0 {
+            return None;
+        }
+        let periods = (series.len() - 1) as f64;
+        let annualized = (1.0 + rel_diff).powf(self.trading_days as f64 / periods) - 1.0;
+        Some((abs_diff, annualized))
+    }
+}
+
+/// The downside deviation of the daily returns: the root mean square of their shortfall below
+/// the daily `target` return, counting returns above it as no shortfall. None without any
+/// return below the target.
+pub struct DownsideDeviation {
+    pub target: f64,
+}
+
+#[async_trait]
+impl StockSignal for DownsideDeviation {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.len() < 2 {
+            return None;
+        }
+        let returns = simple_returns(series);
+        let squares = returns
+            .iter()
+            .map(|r| (r - self.target).min(0.0).powi(2))
+            .sum::<f64>();
+        let deviation = (squares / returns.len() as f64).sqrt();
+        (deviation > 0.0).then_some(deviation)
+    }
+}
+
+/// The mean daily return in excess of the daily `risk_free_rate`, divided by the downside
+/// deviation below the daily `target` return. Unlike the Sharpe ratio, gains don't count as risk.
+pub struct SortinoRatio {
+    pub risk_free_rate: f64,
+    pub target: f64,
+}
+
+#[async_trait]
+impl StockSignal for SortinoRatio {
+    type SignalType = f64;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let deviation = DownsideDeviation {
+            target: self.target,
+        }
+        .calculate(series)
+        .await?;
+        let (mean, _) = mean_and_std_dev(&simple_returns(series));
+        Some((mean - self.risk_free_rate) / deviation)
+    }
+}
+
+/// The stop price of a trailing stop `pct` percent below the highest price so far, for every
+/// price. It ratchets up with each new high and never moves down.
+pub struct TrailingStop {
+    pub pct: f64,
+}
+
+#[async_trait]
+impl StockSignal for TrailingStop {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        if series.is_empty() {
+            return None;
+        }
+        let factor = 1.0 - self.pct / 100.0;
+        Some(
+            series
+                .iter()
+                .scan(f64::MIN, |peak, price| {
+                    *peak = peak.max(*price);
+                    Some(*peak * factor)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The (lower, middle, upper) Keltner channels for the quotes after the first `period`. The
+/// middle line is the `period` EMA of the closes, the others are `multiplier` ATRs away.
+pub struct KeltnerChannels {
+    pub period: usize,
+    pub multiplier: f64,
+}
+
+#[async_trait]
+impl QuoteSignal for KeltnerChannels {
+    type SignalType = Vec<(f64, f64, f64)>;
+
+    async fn calculate(&self, quotes: &[Quote]) -> Option<Self::SignalType> {
+        let atr = Atr {
+            period: self.period,
+        }
+        .calculate(quotes)
+        .await?;
+        let closes: Vec<f64> = quotes.iter().map(|q| q.close).collect();
+        let ema = ExponentialMovingAverage {
+            period: self.period,
+        }
+        .calculate(&closes)
+        .await?;
+        // the ATR needs one quote more than the EMA, so both end with the last quote
+        Some(
+            ema[ema.len() - atr.len()..]
+                .iter()
+                .zip(&atr)
+                .map(|(middle, atr)| {
+                    let width = self.multiplier * atr;
+                    (middle - width, *middle, middle + width)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The sample standard deviation of the daily returns within each window of `window` returns,
+/// i.e. `window + 1` prices, to see how the volatility changed over the period.
+pub struct RollingVolatility {
+    pub window: usize,
+}
+
+#[async_trait]
+impl StockSignal for RollingVolatility {
+    type SignalType = Vec<f64>;
+
+    async fn calculate(&self, series: &[f64]) -> Option<Self::SignalType> {
+        let n = self.window;
+        if n < 2 || series.len() <= n {
+            return None;
+        }
+        let returns = DailyReturns {}.calculate(series).await?;
+        Some(returns.windows(n).map(|w| mean_and_std_dev(w).1).collect())
+    }
+}
+
+/// What a signal is calculated over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignalInput {
+    /// Closing prices, as a `StockSignal`.
+    Closes,
+    /// Volumes, as a `StockSignal`.
+    Volumes,
+    /// Full quotes, as a `QuoteSignal`.
+    Quotes,
+}
+
+/// The name, parameters and a one-line description of a signal, see `SIGNALS`.
+pub struct SignalInfo {
+    pub name: &'static str,
+    /// The fields of the signal's struct.
+    pub parameters: &'static [&'static str],
+    pub input: SignalInput,
+    pub description: &'static str,
+}
+
+/// All signals of this module, in the order they're defined.
+pub const SIGNALS: &[SignalInfo] = &[
+    SignalInfo {
+        name: "PriceDifference",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the absolute and relative change from the first to the last price",
+    },
+    SignalInfo {
+        name: "MinPrice",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the lowest price",
+    },
+    SignalInfo {
+        name: "MaxPrice",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the highest price",
+    },
+    SignalInfo {
+        name: "WindowedSMA",
+        parameters: &["window_size"],
+        input: SignalInput::Closes,
+        description: "the simple moving average over each window",
+    },
+    SignalInfo {
+        name: "ExponentialMovingAverage",
+        parameters: &["period"],
+        input: SignalInput::Closes,
+        description: "the exponential moving average, seeded with the first period's average",
+    },
+    SignalInfo {
+        name: "Rsi",
+        parameters: &["period"],
+        input: SignalInput::Closes,
+        description: "the relative strength index with Wilder's smoothing",
+    },
+    SignalInfo {
+        name: "MaxDrawdown",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the largest peak-to-trough decline as a negative fraction",
+    },
+    SignalInfo {
+        name: "DailyReturns",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the simple return from each price to the next",
+    },
+    SignalInfo {
+        name: "Volatility",
+        parameters: &["trading_days"],
+        input: SignalInput::Closes,
+        description: "the standard deviation of the daily returns, annualized if asked",
+    },
+    SignalInfo {
+        name: "SharpeRatio",
+        parameters: &["risk_free_rate", "trading_days"],
+        input: SignalInput::Closes,
+        description: "the mean excess return per standard deviation of the returns",
+    },
+    SignalInfo {
+        name: "BollingerBands",
+        parameters: &["period", "num_std"],
+        input: SignalInput::Closes,
+        description: "the SMA with bands num_std standard deviations away",
+    },
+    SignalInfo {
+        name: "Macd",
+        parameters: &["fast", "slow", "signal"],
+        input: SignalInput::Closes,
+        description: "the macd line, signal line and histogram of the fast and slow EMAs",
+    },
+    SignalInfo {
+        name: "AverageVolume",
+        parameters: &[],
+        input: SignalInput::Volumes,
+        description: "the average shares traded per period",
+    },
+    SignalInfo {
+        name: "RateOfChange",
+        parameters: &["period"],
+        input: SignalInput::Closes,
+        description: "the relative change to the price period steps earlier",
+    },
+    SignalInfo {
+        name: "Atr",
+        parameters: &["period"],
+        input: SignalInput::Quotes,
+        description: "the average true range with Wilder's smoothing",
+    },
+    SignalInfo {
+        name: "SmaCross",
+        parameters: &["short", "long"],
+        input: SignalInput::Closes,
+        description: "the golden and death crosses of a short and a long SMA",
+    },
+    SignalInfo {
+        name: "ZScore",
+        parameters: &["period"],
+        input: SignalInput::Closes,
+        description: "the standard deviations of each price from its window's mean",
+    },
+    SignalInfo {
+        name: "MedianPrice",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the median price",
+    },
+    SignalInfo {
+        name: "CumulativeReturn",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the total return from the first price up to each price",
+    },
+    SignalInfo {
+        name: "LinearTrend",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the slope per period and R² of a least-squares fit",
+    },
+    SignalInfo {
+        name: "StochasticOscillator",
+        parameters: &["k_period", "d_period"],
+        input: SignalInput::Quotes,
+        description: "%K, the close within the recent range, and its average %D",
+    },
+    SignalInfo {
+        name: "OnBalanceVolume",
+        parameters: &[],
+        input: SignalInput::Quotes,
+        description: "the running total of the volume, signed by the close's direction",
+    },
+    SignalInfo {
+        name: "WeightedMovingAverage",
+        parameters: &["window_size"],
+        input: SignalInput::Closes,
+        description: "the moving average weighting recent prices more",
+    },
+    SignalInfo {
+        name: "PercentileRank",
+        parameters: &[],
+        input: SignalInput::Closes,
+        description: "the share of prices below the last one, from 0 to 100",
+    },
+    SignalInfo {
+        name: "AnnualizedReturn",
+        parameters: &["trading_days"],
+        input: SignalInput::Closes,
+        description: "the absolute change and the compounded relative change per year",
+    },
+    SignalInfo {
+        name: "DownsideDeviation",
+        parameters: &["target"],
+        input: SignalInput::Closes,
+        description: "the root mean square of the daily returns' shortfall below a target",
+    },
+    SignalInfo {
+        name: "SortinoRatio",
+        parameters: &["risk_free_rate", "target"],
+        input: SignalInput::Closes,
+        description: "the mean excess return per downside deviation",
+    },
+    SignalInfo {
+        name: "TrailingStop",
+        parameters: &["pct"],
+        input: SignalInput::Closes,
+        description: "the stop price pct percent below the highest price so far",
+    },
+    SignalInfo {
+        name: "KeltnerChannels",
+        parameters: &["period", "multiplier"],
+        input: SignalInput::Quotes,
+        description: "the EMA with channels multiplier ATRs away",
+    },
+    SignalInfo {
+        name: "RollingVolatility",
+        parameters: &["window"],
+        input: SignalInput::Closes,
+        description: "the standard deviation of the daily returns within each window",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    #[async_std::test]
+    async fn test_PriceDifference_calculate() {
+        let signal = PriceDifference {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, Some((0.0, 0.0)));
+        assert_eq!(signal.calculate(&[1.0, 0.0]).await, Some((-1.0, -1.0)));
+        assert_eq!(
+            signal
+                .calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0])
+                .await,
+            Some((8.0, 4.0))
+        );
+        assert_eq!(
+            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]).await,
+            Some((1.0, 1.0))
+        );
+    }
+
+    #[async_std::test]
+    async fn test_MinPrice_calculate() {
+        let signal = MinPrice {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, Some(1.0));
+        assert_eq!(signal.calculate(&[1.0, 0.0]).await, Some(0.0));
+        assert_eq!(
+            signal
+                .calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0])
+                .await,
+            Some(1.0)
+        );
+        assert_eq!(
+            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]).await,
+            Some(0.0)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_MaxPrice_calculate() {
+        let signal = MaxPrice {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, Some(1.0));
+        assert_eq!(signal.calculate(&[1.0, 0.0]).await, Some(1.0));
+        assert_eq!(
+            signal
+                .calculate(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0])
+                .await,
+            Some(10.0)
+        );
+        assert_eq!(
+            signal.calculate(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]).await,
+            Some(6.0)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_WindowedSMA_calculate() {
+        let series = vec![2.0, 4.5, 5.3, 6.5, 4.7];
+
+        let signal = WindowedSMA { window_size: 3 };
+        assert_eq!(
+            signal.calculate(&series).await,
+            Some(vec![3.9333333333333336, 5.433333333333334, 5.5])
+        );
+
+        let signal = WindowedSMA { window_size: 5 };
+        assert_eq!(signal.calculate(&series).await, Some(vec![4.6]));
+
+        let signal = WindowedSMA { window_size: 10 };
+        assert_eq!(signal.calculate(&series).await, Some(vec![]));
+    }
+
+    #[async_std::test]
+    async fn test_ExponentialMovingAverage_calculate() {
+        let series = vec![2.0, 4.0, 6.0, 8.0, 10.0];
+
+        let signal = ExponentialMovingAverage { period: 0 };
+        assert_eq!(signal.calculate(&series).await, None);
+
+        let signal = ExponentialMovingAverage { period: 3 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&series).await, Some(vec![4.0, 6.0, 8.0]));
+
+        let signal = ExponentialMovingAverage { period: 1 };
+        assert_eq!(signal.calculate(&series).await, Some(series.clone()));
+
+        let signal = ExponentialMovingAverage { period: 10 };
+        assert_eq!(signal.calculate(&series).await, Some(vec![]));
+    }
+
+    #[async_std::test]
+    async fn test_Rsi_calculate() {
+        // reference series from the StockCharts RSI walkthrough, values at full precision
+        let series = vec![
+            44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03,
+            45.61, 46.28, 46.28, 46.00, 46.03, 46.41, 46.22, 45.64,
+        ];
+        let expected = [70.46, 66.25, 66.48, 69.35, 66.29, 57.92];
+
+        let signal = Rsi { period: 14 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&series[..14]).await, None);
+        let rsi = signal.calculate(&series).await.unwrap();
+        assert_eq!(rsi.len(), expected.len());
+        for (actual, expected) in rsi.iter().zip(expected.iter()) {
+            assert!((actual - expected).abs() < 0.01);
+        }
+
+        let signal = Rsi { period: 3 };
+        assert_eq!(
+            signal.calculate(&[1.0, 2.0, 3.0, 4.0, 5.0]).await,
+            Some(vec![100.0, 100.0])
+        );
+
+        let signal = Rsi { period: 0 };
+        assert_eq!(signal.calculate(&series).await, None);
+    }
+
+    #[async_std::test]
+    async fn test_MaxDrawdown_calculate() {
+        let signal = MaxDrawdown {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, Some(0.0));
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0, 4.0]).await, Some(0.0));
+        assert_eq!(
+            signal.calculate(&[100.0, 120.0, 90.0, 110.0, 100.0]).await,
+            Some(-0.25)
+        );
+        assert_eq!(
+            signal.calculate(&[10.0, 5.0, 20.0, 15.0, 25.0]).await,
+            Some(-0.5)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_Volatility_calculate() {
+        let signal = Volatility { trading_days: None };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 1.0]).await, Some(0.0));
+        assert_eq!(signal.calculate(&[5.0, 5.0, 5.0, 5.0]).await, Some(0.0));
+
+        // returns of 10%, -10% and 10%
+        let series = [100.0, 110.0, 99.0, 108.9];
+        let daily = signal.calculate(&series).await.unwrap();
+        assert!((daily - 0.115470).abs() < 1e-6);
+
+        let signal = Volatility {
+            trading_days: Some(252),
+        };
+        let annualized = signal.calculate(&series).await.unwrap();
+        assert!((annualized - daily * 252f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[async_std::test]
+    async fn test_SharpeRatio_calculate() {
+        let signal = SharpeRatio {
+            risk_free_rate: 0.0,
+            trading_days: None,
+        };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(signal.calculate(&[5.0, 5.0, 5.0]).await, None);
+
+        // returns of 10%, -10% and 10%: mean 1/30, standard deviation 1/sqrt(75)
+        let series = [100.0, 110.0, 99.0, 108.9];
+        let sharpe = signal.calculate(&series).await.unwrap();
+        assert!((sharpe - 0.288675).abs() < 1e-6);
+
+        let signal = SharpeRatio {
+            risk_free_rate: 0.01,
+            trading_days: None,
+        };
+        let sharpe = signal.calculate(&series).await.unwrap();
+        assert!((sharpe - 0.202073).abs() < 1e-6);
+
+        let signal = SharpeRatio {
+            risk_free_rate: 0.01,
+            trading_days: Some(252),
+        };
+        let annualized = signal.calculate(&series).await.unwrap();
+        assert!((annualized - sharpe * 252f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[async_std::test]
+    async fn test_BollingerBands_calculate() {
+        let signal = BollingerBands {
+            period: 3,
+            num_std: 2.0,
+        };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(
+            signal.calculate(&[1.0, 2.0, 3.0, 4.0, 5.0]).await,
+            Some(vec![(0.0, 2.0, 4.0), (1.0, 3.0, 5.0), (2.0, 4.0, 6.0)])
+        );
+        assert_eq!(
+            signal.calculate(&[3.0, 3.0, 3.0]).await,
+            Some(vec![(3.0, 3.0, 3.0)])
+        );
+
+        let signal = BollingerBands {
+            period: 1,
+            num_std: 2.0,
+        };
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+    }
+
+    #[async_std::test]
+    async fn test_Macd_calculate() {
+        let signal = Macd {
+            fast: 2,
+            slow: 3,
+            signal: 2,
+        };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0]).await, Some(vec![]));
+
+        let macd = signal
+            .calculate(&[1.0, 2.0, 3.0, 4.0, 5.0, 4.0, 3.0])
+            .await
+            .unwrap();
+        let expected = [
+            (0.5, 0.5, 0.0),
+            (0.5, 0.5, 0.0),
+            (0.166667, 0.277778, -0.111111),
+            (-0.111111, 0.018519, -0.129630),
+        ];
+        assert_eq!(macd.len(), expected.len());
+        for (actual, expected) in macd.iter().zip(expected.iter()) {
+            assert!((actual.0 - expected.0).abs() < 1e-6);
+            assert!((actual.1 - expected.1).abs() < 1e-6);
+            assert!((actual.2 - expected.2).abs() < 1e-6);
+        }
+
+        let signal = Macd {
+            fast: 3,
+            slow: 3,
+            signal: 2,
+        };
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0, 4.0]).await, None);
+
+        let signal = Macd::default();
+        assert_eq!((signal.fast, signal.slow, signal.signal), (12, 26, 9));
+        assert_eq!(signal.calculate(&[1.0; 25]).await, None);
+        assert_eq!(signal.calculate(&[1.0; 26]).await, Some(vec![]));
+        assert_eq!(signal.calculate(&[1.0; 32]).await, Some(vec![]));
+        assert_eq!(signal.calculate(&[1.0; 33]).await, Some(vec![]));
+        assert_eq!(signal.calculate(&[1.0; 34]).await.unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn test_AverageVolume_calculate() {
+        let signal = AverageVolume {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1000.0]).await, Some(1000.0));
+        assert_eq!(signal.calculate(&[100.0, 0.0, 500.0]).await, Some(200.0));
+    }
+
+    #[async_std::test]
+    async fn test_RateOfChange_calculate() {
+        let signal = RateOfChange { period: 2 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(RateOfChange { period: 0 }.calculate(&[1.0]).await, None);
+        assert_eq!(signal.calculate(&[0.0; 4]).await, Some(vec![0.0, 0.0]));
+        assert_eq!(
+            signal.calculate(&[1.0, 2.0, 3.0, 4.0, 5.0]).await,
+            Some(vec![2.0, 1.0, 2.0 / 3.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_Atr_calculate() {
+        let quote = |high, low, close| Quote {
+            timestamp: chrono::Utc::now(),
+            high,
+            low,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let quotes = [
+            quote(11.0, 9.0, 10.0),
+            quote(12.0, 10.0, 11.0), // TR 2
+            quote(11.5, 11.0, 11.0), // TR 0.5
+            quote(15.0, 14.0, 14.5), // gap up, TR 4
+        ];
+        let signal = Atr { period: 2 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&quotes[..2]).await, None);
+        assert_eq!(Atr { period: 0 }.calculate(&quotes).await, None);
+        assert_eq!(
+            signal.calculate(&quotes).await,
+            Some(vec![1.25, (1.25 + 4.0) / 2.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_SmaCross_calculate() {
+        let signal = SmaCross { short: 2, long: 3 };
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(
+            SmaCross { short: 3, long: 3 }.calculate(&[1.0; 5]).await,
+            None
+        );
+        assert_eq!(signal.calculate(&[1.0; 5]).await, Some(vec![]));
+
+        let series = [5.0, 4.0, 3.0, 2.0, 6.0, 6.0, 2.0, 1.0];
+        assert_eq!(
+            signal.calculate(&series).await,
+            Some(vec![
+                (4, CrossDirection::Golden),
+                (6, CrossDirection::Death)
+            ])
+        );
+        // the SMAs meet while flat, but the short one stays below
+        let touch = [3.0, 2.0, 1.0, 1.0, 1.0, 1.0, 0.0];
+        assert_eq!(signal.calculate(&touch).await, Some(vec![]));
+    }
+
+    #[async_std::test]
+    async fn test_ZScore_calculate() {
+        let signal = ZScore { period: 3 };
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(ZScore { period: 1 }.calculate(&[1.0, 2.0]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0, 2.0, 2.0]).await, None);
+        assert_eq!(
+            signal.calculate(&[1.0, 2.0, 3.0, 1.0]).await,
+            Some(vec![1.0, -1.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_MedianPrice_calculate() {
+        let signal = MedianPrice {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[3.0]).await, Some(3.0));
+        assert_eq!(signal.calculate(&[3.0, 100.0, 1.0]).await, Some(3.0));
+        let series = [4.0, 1.0, 3.0, 2.0];
+        assert_eq!(signal.calculate(&series).await, Some(2.5));
+        assert_eq!(series, [4.0, 1.0, 3.0, 2.0]);
+    }
+
+    #[async_std::test]
+    async fn test_CumulativeReturn_calculate() {
+        let signal = CumulativeReturn {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[0.0, 1.0]).await, Some(vec![0.0, 1.0]));
+        assert_eq!(
+            signal.calculate(&[2.0, 3.0, 1.0, 2.5]).await,
+            Some(vec![0.0, 0.5, -0.5, 0.25])
+        );
+
+        let series = [131.0, 128.5, 126.0, 130.9, 132.1, 128.9];
+        let (_, rel_change) = PriceDifference {}.calculate(&series).await.unwrap();
+        let returns = signal.calculate(&series).await.unwrap();
+        assert_eq!(returns.last(), Some(&rel_change));
+    }
+
+    #[async_std::test]
+    async fn test_LinearTrend_calculate() {
+        let signal = LinearTrend {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(
+            signal.calculate(&[1.0, 3.0, 5.0, 7.0]).await,
+            Some((2.0, 1.0))
+        );
+        assert_eq!(signal.calculate(&[4.0, 3.0]).await, Some((-1.0, 1.0)));
+        assert_eq!(signal.calculate(&[5.0, 5.0, 5.0]).await, Some((0.0, 1.0)));
+
+        let (slope, r_squared) = signal.calculate(&[1.0, 3.0, 2.0, 4.0]).await.unwrap();
+        assert!((slope - 0.8).abs() < 1e-9);
+        assert!((r_squared - 0.64).abs() < 1e-9);
+    }
+
+    #[async_std::test]
+    async fn test_StochasticOscillator_calculate() {
+        let quote = |high, low, close| Quote {
+            timestamp: chrono::Utc::now(),
+            high,
+            low,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let quotes = [
+            quote(10.0, 8.0, 9.0),
+            quote(12.0, 9.0, 12.0),  // %K 100
+            quote(11.0, 10.0, 10.5), // %K 50
+            quote(12.0, 11.0, 12.0), // %K 100
+        ];
+        let signal = StochasticOscillator {
+            k_period: 2,
+            d_period: 2,
+        };
+        assert_eq!(signal.calculate(&quotes[..2]).await, None);
+        assert_eq!(
+            StochasticOscillator {
+                k_period: 0,
+                d_period: 2
+            }
+            .calculate(&quotes)
+            .await,
+            None
+        );
+        assert_eq!(
+            signal.calculate(&quotes).await,
+            Some(vec![(50.0, 75.0), (100.0, 75.0)])
+        );
+        let flat = vec![quote(5.0, 5.0, 5.0); 3];
+        assert_eq!(signal.calculate(&flat).await, Some(vec![(50.0, 50.0)]));
+    }
+
+    #[async_std::test]
+    async fn test_OnBalanceVolume_calculate() {
+        let quote = |close, volume| Quote {
+            timestamp: chrono::Utc::now(),
+            high: close,
+            low: close,
+            close,
+            volume,
+            adjustment: None,
+        };
+        let signal = OnBalanceVolume {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[quote(10.0, 100)]).await, None);
+        let quotes = [
+            quote(10.0, 100),
+            quote(11.0, 200), // up, +200
+            quote(10.5, 150), // down, -150
+            quote(10.5, 300), // unchanged
+            quote(12.0, 50),  // up, +50
+        ];
+        assert_eq!(
+            signal.calculate(&quotes).await,
+            Some(vec![0.0, 200.0, 50.0, 50.0, 100.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_WeightedMovingAverage_calculate() {
+        let signal = WeightedMovingAverage { window_size: 3 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(
+            WeightedMovingAverage { window_size: 1 }
+                .calculate(&[1.0])
+                .await,
+            None
+        );
+        assert_eq!(signal.calculate(&[1.0, 2.0]).await, Some(vec![]));
+        // (1 * 1 + 2 * 2 + 3 * 6) / 6 and (1 * 2 + 2 * 6 + 3 * 3) / 6
+        assert_eq!(
+            signal.calculate(&[1.0, 2.0, 6.0, 3.0]).await,
+            Some(vec![23.0 / 6.0, 23.0 / 6.0])
+        );
+        assert_eq!(
+            WeightedMovingAverage { window_size: 2 }
+                .calculate(&[3.0, 6.0, 3.0])
+                .await,
+            Some(vec![5.0, 4.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_PercentileRank_calculate() {
+        let signal = PercentileRank {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[5.0]).await, Some(0.0));
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0, 4.0]).await, Some(75.0));
+        assert_eq!(signal.calculate(&[4.0, 3.0, 2.0, 1.0]).await, Some(0.0));
+        assert_eq!(
+            signal.calculate(&[2.0, 1.0, 2.0, 3.0, 2.0]).await,
+            Some(20.0)
+        );
+    }
+
+    #[async_std::test]
+    async fn test_AnnualizedReturn_calculate() {
+        let signal = AnnualizedReturn { trading_days: 252 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(
+            AnnualizedReturn { trading_days: 0 }
+                .calculate(&[1.0, 2.0])
+                .await,
+            None
+        );
+        // 10% over exactly a year stays at 10%
+        let year: Vec<f64> = (0..=252).map(|i| 1.0 + 0.1 * i as f64 / 252.0).collect();
+        let (abs_diff, annualized) = signal.calculate(&year).await.unwrap();
+        assert!((abs_diff - 0.1).abs() < 1e-9);
+        assert!((annualized - 0.1).abs() < 1e-9);
+        // 10% over half a year compounds to 21%
+        let (_, annualized) = AnnualizedReturn { trading_days: 2 }
+            .calculate(&[100.0, 110.0])
+            .await
+            .unwrap();
+        assert!((annualized - 0.21).abs() < 1e-9);
+        assert_eq!(signal.calculate(&[2.0, 2.0]).await, Some((0.0, 0.0)));
+        assert_eq!(signal.calculate(&[2.0, 0.0]).await, Some((-2.0, -1.0)));
+        assert_eq!(signal.calculate(&[2.0, -1.0]).await, None);
+    }
+
+    #[async_std::test]
+    async fn test_DownsideDeviation_calculate() {
+        let signal = DownsideDeviation { target: 0.0 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0]).await, None);
+
+        // returns of 10%, -10% and 10%: only the -10% falls short
+        let series = [100.0, 110.0, 99.0, 108.9];
+        let deviation = signal.calculate(&series).await.unwrap();
+        assert!((deviation - (0.01_f64 / 3.0).sqrt()).abs() < 1e-9);
+        // and falls short of a 5% target by 15%
+        let deviation = DownsideDeviation { target: 0.05 }
+            .calculate(&series)
+            .await
+            .unwrap();
+        assert!((deviation - (0.0225_f64 / 3.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[async_std::test]
+    async fn test_SortinoRatio_calculate() {
+        let signal = SortinoRatio {
+            risk_free_rate: 0.0,
+            target: 0.0,
+        };
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(signal.calculate(&[5.0, 5.0, 5.0]).await, None);
+        assert_eq!(signal.calculate(&[1.0, 2.0, 3.0]).await, None);
+
+        // mean return 1/30 over a downside deviation of sqrt(0.01 / 3)
+        let series = [100.0, 110.0, 99.0, 108.9];
+        let sortino = signal.calculate(&series).await.unwrap();
+        assert!((sortino - 0.577350).abs() < 1e-6);
+        let sharpe = SharpeRatio {
+            risk_free_rate: 0.0,
+            trading_days: None,
+        };
+        assert!(sortino > sharpe.calculate(&series).await.unwrap());
+
+        let signal = SortinoRatio {
+            risk_free_rate: 0.01,
+            target: 0.0,
+        };
+        let sortino = signal.calculate(&series).await.unwrap();
+        assert!((sortino - 0.404145).abs() < 1e-6);
+    }
+
+    #[async_std::test]
+    async fn test_TrailingStop_calculate() {
+        let signal = TrailingStop { pct: 10.0 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[50.0]).await, Some(vec![45.0]));
+
+        // rises to 120, then falls through the stop at 108
+        let series = [100.0, 110.0, 105.0, 120.0, 112.0, 107.0];
+        let stops = signal.calculate(&series).await.unwrap();
+        let expected = [90.0, 99.0, 99.0, 108.0, 108.0, 108.0];
+        assert_eq!(stops.len(), expected.len());
+        for (stop, expected) in stops.iter().zip(expected) {
+            assert!((stop - expected).abs() < 1e-9);
+        }
+        assert!(stops.windows(2).all(|w| w[1] >= w[0]));
+        assert!(series[5] < stops[5]);
+
+        let tight = TrailingStop { pct: 0.0 };
+        assert_eq!(
+            tight.calculate(&[1.0, 3.0, 2.0]).await,
+            Some(vec![1.0, 3.0, 3.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_KeltnerChannels_calculate() {
+        let quote = |high, low, close| Quote {
+            timestamp: chrono::Utc::now(),
+            high,
+            low,
+            close,
+            volume: 0,
+            adjustment: None,
+        };
+        let quotes = [
+            quote(11.0, 9.0, 10.0),
+            quote(12.0, 10.0, 11.0), // ATR 1.25
+            quote(11.5, 11.0, 11.0),
+            quote(15.0, 14.0, 14.5), // ATR 2.625
+        ];
+        let signal = KeltnerChannels {
+            period: 2,
+            multiplier: 2.0,
+        };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&quotes[..2]).await, None);
+        let channels = signal.calculate(&quotes).await.unwrap();
+        let expected = [
+            (8.333333, 10.833333, 13.333333),
+            (8.027778, 13.277778, 18.527778),
+        ];
+        assert_eq!(channels.len(), expected.len());
+        for (actual, expected) in channels.iter().zip(expected) {
+            assert!((actual.0 - expected.0).abs() < 1e-6);
+            assert!((actual.1 - expected.1).abs() < 1e-6);
+            assert!((actual.2 - expected.2).abs() < 1e-6);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_DailyReturns_calculate() {
+        let signal = DailyReturns {};
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[1.0]).await, None);
+        assert_eq!(
+            signal.calculate(&[2.0, 3.0, 1.5]).await,
+            Some(vec![0.5, -0.5])
+        );
+        // no division by zero after a price of 0.0
+        assert_eq!(
+            signal.calculate(&[1.0, 0.0, 2.0]).await,
+            Some(vec![-1.0, 2.0])
+        );
+    }
+
+    #[async_std::test]
+    async fn test_RollingVolatility_calculate() {
+        let signal = RollingVolatility { window: 3 };
+        assert_eq!(signal.calculate(&[]).await, None);
+        assert_eq!(signal.calculate(&[2.0, 2.0, 3.0]).await, None);
+        assert_eq!(
+            RollingVolatility { window: 1 }.calculate(&[1.0; 5]).await,
+            None
+        );
+        // returns of 0%, 50% and 100%
+        assert_eq!(
+            signal.calculate(&[2.0, 2.0, 3.0, 6.0]).await,
+            Some(vec![0.5])
+        );
+        assert_eq!(signal.calculate(&[1.0; 5]).await, Some(vec![0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_SIGNALS() {
+        let mut names: Vec<&str> = SIGNALS.iter().map(|s| s.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), SIGNALS.len());
+        assert!(SIGNALS.iter().all(|s| !s.description.is_empty()));
+        let keltner = SIGNALS
+            .iter()
+            .find(|s| s.name == "KeltnerChannels")
+            .unwrap();
+        assert_eq!(keltner.parameters, ["period", "multiplier"]);
+        assert_eq!(keltner.input, SignalInput::Quotes);
+    }
+}
@@ -0,0 +1,341 @@
+//!
+//! End-to-end runs of the CLI on canned quotes read with `--from-csv`, without any network.
+//!
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+///
+/// A directory with a CSV file per symbol, removed again when dropped.
+///
+struct MockProvider {
+    dir: PathBuf,
+}
+
+impl MockProvider {
+    fn new(name: &str, files: &[(&str, &str)]) -> Self {
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (symbol, content) in files {
+            std::fs::write(dir.join(format!("{}.csv", symbol)), content).unwrap();
+        }
+        MockProvider { dir }
+    }
+
+    ///
+    /// The CLI over the January 2021 quotes of the symbols.
+    ///
+    fn command(&self, symbols: &str) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_manning-lp-async-rust-project-1-m1"));
+        command
+            .args(["--quiet", "--sorted", "--no-cache", "--sma-window", "2"])
+            .args(["--from", "2021-01-01", "--to", "2021-01-31T00:00:00Z"])
+            .args([
+                "--symbols",
+                symbols,
+                "--from-csv",
+                self.dir.to_str().unwrap(),
+            ]);
+        command
+    }
+
+    ///
+    /// Run the CLI over the symbols, keeping the canned series that are shorter than any
+    /// default window.
+    ///
+    fn run(&self, symbols: &str, extra_args: &[&str]) -> Output {
+        self.command(symbols)
+            .args(["--min-points", "0"])
+            .args(extra_args)
+            .output()
+            .unwrap()
+    }
+
+    ///
+    /// Like `run`, with the warnings logged despite `--quiet`.
+    ///
+    fn run_logged(&self, symbols: &str, extra_args: &[&str]) -> Output {
+        self.command(symbols)
+            .args(["--min-points", "0"])
+            .args(extra_args)
+            .env("RUST_LOG", "warn")
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for MockProvider {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+const AAPL: &str = "Date,AdjClose\n2021-01-04,1.0\n2021-01-05,2.0\n2021-01-06,3.0\n";
+const MSFT: &str = "Date,AdjClose\n2021-01-04,4.0\n2021-01-05,3.0\n";
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn test_csv_output() {
+    let provider = MockProvider::new("test_cli_csv_output", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let output = provider.run("AAPL,MSFT", &[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "period start,symbol,price,change %,min,max,2d avg,30d ema\n\
+         2021-01-01T00:00:00+00:00,AAPL,$3.00,200.00%,$1.00,$3.00,$2.50,\n\
+         2021-01-01T00:00:00+00:00,MSFT,$3.00,-25.00%,$3.00,$4.00,$3.50,\n"
+    );
+
+    // the periods of the averages are in the unit of the interval
+    let output = provider.run("AAPL", &["--interval", "weekly"]);
+    assert!(
+        stdout(&output).starts_with("period start,symbol,price,change %,min,max,2w avg,30w ema\n")
+    );
+}
+
+#[test]
+fn test_json_output() {
+    let provider = MockProvider::new("test_cli_json_output", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let output = provider.run("MSFT AAPL", &["--output-format", "json"]);
+    assert_eq!(output.status.code(), Some(0));
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json[0]["symbol"], "MSFT");
+    assert_eq!(json[0]["change_pct"], -25.0);
+    assert_eq!(json[1]["symbol"], "AAPL");
+    assert_eq!(json[1]["sma"], serde_json::json!([1.5, 2.5]));
+}
+
+#[test]
+fn test_empty_series() {
+    let provider = MockProvider::new(
+        "test_cli_empty_series",
+        &[("AAPL", AAPL), ("EMPTY", "Date,AdjClose\n")],
+    );
+    let output = provider.run("AAPL,EMPTY", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output).lines().count(), 2);
+    assert!(stderr(&output).contains("no data for EMPTY"));
+
+    let output = provider.run("EMPTY", &[]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(stdout(&output).lines().count(), 1);
+}
+
+#[test]
+fn test_fetch_error() {
+    let provider = MockProvider::new(
+        "test_cli_fetch_error",
+        &[("AAPL", AAPL), ("BROKEN", "Date,AdjClose\n2021-01-04,x\n")],
+    );
+    let output = provider.run("AAPL,BROKEN", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains(",AAPL,"));
+    let errors = stderr(&output);
+    assert!(errors.contains("1 of 2 symbols failed"));
+    assert!(errors.contains("BROKEN: couldn't read the data file"));
+}
+
+#[test]
+fn test_append_output() {
+    let provider = MockProvider::new("test_cli_append_output", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let path = provider.dir.join("out.csv");
+    let path = path.to_str().unwrap();
+    for symbol in ["AAPL", "MSFT"] {
+        let output = provider.run(symbol, &["--output", path, "--append"]);
+        assert_eq!(output.status.code(), Some(0));
+    }
+    let content = std::fs::read_to_string(path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("period start,"));
+    assert!(lines[1].contains(",AAPL,"));
+    assert!(lines[2].contains(",MSFT,"));
+
+    // without --append the file starts over
+    provider.run("MSFT", &["--output", path]);
+    assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 2);
+}
+
+#[test]
+fn test_delimiters() {
+    let provider = MockProvider::new("test_cli_delimiters", &[("AAPL", AAPL)]);
+    let columns = ["--columns", "symbol,last_price,change"];
+    let output = provider.run(
+        "AAPL",
+        &[&columns[..], &["--output-format", "tsv"]].concat(),
+    );
+    assert_eq!(
+        stdout(&output),
+        "symbol\tprice\tchange %\nAAPL\t$3.00\t200.00%\n"
+    );
+
+    let output = provider.run("AAPL", &[&columns[..], &["--delimiter", ";"]].concat());
+    assert_eq!(
+        stdout(&output),
+        "symbol;price;change %\nAAPL;$3.00;200.00%\n"
+    );
+}
+
+#[test]
+fn test_no_header() {
+    let provider = MockProvider::new("test_cli_no_header", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let output = provider.run("AAPL,MSFT", &["--no-header"]);
+    assert_eq!(output.status.code(), Some(0));
+    let with_header = stdout(&provider.run("AAPL,MSFT", &[]));
+    assert_eq!(stdout(&output), with_header.split_once('\n').unwrap().1);
+}
+
+#[test]
+fn test_warn_on_jumps() {
+    // a 2:1 split on the 5th, which the adjusted closes don't show
+    let split = "Date,Close,AdjClose\n2021-01-04,100.0,50.0\n2021-01-05,50.0,50.0\n\
+                 2021-01-06,26.0,26.0\n";
+    let provider = MockProvider::new("test_cli_warn_on_jumps", &[("AAPL", AAPL), ("SPLT", split)]);
+    let output = provider.run_logged("AAPL", &["--warn-on-jumps"]);
+    assert_eq!(output.status.code(), Some(0));
+    let warnings = stderr(&output);
+    assert!(warnings.contains("AAPL moved +100.0% on 2021-01-05"));
+    assert!(warnings.contains("AAPL moved +50.0% on 2021-01-06"));
+
+    let output = provider.run_logged("AAPL", &["--warn-on-jumps", "60"]);
+    assert_eq!(stderr(&output).lines().count(), 1);
+    assert_eq!(stderr(&provider.run_logged("AAPL", &[])), "");
+
+    // with both closes only the split is warned about, not the real drop after it
+    let warnings = stderr(&provider.run_logged("SPLT", &["--warn-on-jumps"]));
+    assert!(warnings
+        .contains("SPLT's unadjusted close moved -50.0% against the adjusted one on 2021-01-05"));
+    assert_eq!(warnings.lines().count(), 1);
+}
+
+#[test]
+fn test_portfolio() {
+    let provider = MockProvider::new("test_cli_portfolio", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let columns = ["--columns", "symbol,last_price,change,min,max,sma"];
+    let output = provider.run("AAPL,MSFT", &[&columns[..], &["--portfolio"]].concat());
+    assert_eq!(output.status.code(), Some(0));
+    // AAPL doubles and MSFT loses a quarter up to the last common day
+    assert_eq!(
+        stdout(&output).lines().last(),
+        Some("PORTFOLIO,$137.50,37.50%,$100.00,$137.50,$118.75")
+    );
+
+    let weighted = ["--portfolio", "--weights", "3,1"];
+    let output = provider.run("AAPL,MSFT", &[&columns[..], &weighted].concat());
+    assert!(stdout(&output).ends_with("\nPORTFOLIO,$168.75,68.75%,$100.00,$168.75,$134.38\n"));
+
+    let output = provider.run("AAPL,MSFT", &["--portfolio", "--weights", "1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("a weight per symbol"));
+}
+
+#[test]
+fn test_alert_threshold() {
+    let provider = MockProvider::new(
+        "test_cli_alert_threshold",
+        &[("AAPL", AAPL), ("MSFT", MSFT)],
+    );
+    let columns = ["--columns", "symbol,change", "--portfolio"];
+    // AAPL is up 200%, MSFT down 25% and the PORTFOLIO up 37.50%
+    let output = provider.run(
+        "AAPL,MSFT",
+        &[&columns[..], &["--alert-threshold", "50"]].concat(),
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "symbol,change %\nAAPL,200.00%\n");
+
+    let output = provider.run(
+        "AAPL,MSFT",
+        &[&columns[..], &["--alert-threshold", "30"]].concat(),
+    );
+    assert_eq!(
+        stdout(&output),
+        "symbol,change %\nAAPL,200.00%\nPORTFOLIO,37.50%\n"
+    );
+}
+
+#[test]
+fn test_compare_prior() {
+    let prior = "Date,AdjClose\n2020-12-15,2.0\n2020-12-30,1.0\n2021-01-04,1.0\n2021-01-05,1.5\n";
+    let provider = MockProvider::new("test_cli_compare_prior", &[("AAPL", prior)]);
+    let columns = ["--columns", "symbol,change,prior_change,change_vs_prior"];
+    let output = provider.run("AAPL", &[&columns[..], &["--compare-prior"]].concat());
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "symbol,change %,prior change %,vs prior\nAAPL,50.00%,-50.00%,+100.00\n"
+    );
+
+    // by default the prior change goes next to the change
+    let output = provider.run("AAPL", &["--compare-prior"]);
+    assert!(
+        stdout(&output).starts_with("period start,symbol,price,change %,prior change %,vs prior,")
+    );
+}
+
+#[test]
+fn test_min_points() {
+    let provider = MockProvider::new("test_cli_min_points", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let output = provider
+        .command("AAPL,MSFT")
+        .args(["--min-points", "3"])
+        .env("RUST_LOG", "warn")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output).lines().count(), 2);
+    assert!(stdout(&output).contains(",AAPL,"));
+    let warnings = stderr(&output);
+    assert!(warnings.contains("skipping MSFT, it has 2 data points, fewer than the minimum of 3"));
+    assert_eq!(warnings.lines().count(), 1);
+    // --quiet leaves the warnings out unless RUST_LOG asks for them
+    let output = provider.run("AAPL,MSFT", &["--min-points", "3"]);
+    assert_eq!(stderr(&output), "");
+
+    // by default as many as the 30 day EMA takes
+    let output = provider
+        .command("AAPL,MSFT")
+        .env("RUST_LOG", "warn")
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output).lines().count(), 1);
+    assert!(stderr(&output)
+        .contains("skipping AAPL, it has 3 data points, fewer than the minimum of 30"));
+}
+
+#[test]
+fn test_json_lines_output() {
+    let provider = MockProvider::new("test_cli_json_lines", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let output = provider.run("AAPL,MSFT", &["--output-format", "jsonl"]);
+    assert_eq!(output.status.code(), Some(0));
+    let lines: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["symbol"], "AAPL");
+    assert_eq!(lines[1]["change_pct"], -25.0);
+
+    // the same fields as the JSON array
+    let output = provider.run("AAPL,MSFT", &["--output-format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(json[0], lines[0]);
+    assert_eq!(json[1], lines[1]);
+}
+
+#[test]
+fn test_progress_without_terminal() {
+    let provider = MockProvider::new("test_cli_progress", &[("AAPL", AAPL), ("MSFT", MSFT)]);
+    let plain = provider.run("AAPL,MSFT", &[]);
+    let output = provider.run("AAPL,MSFT", &["--progress"]);
+    assert_eq!(output.status.code(), Some(0));
+    // stderr isn't a terminal here, so there's no bar and the data is untouched
+    assert_eq!(stdout(&output), stdout(&plain));
+    assert_eq!(stderr(&output), "");
+}